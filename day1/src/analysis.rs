@@ -0,0 +1,290 @@
+use num_bigint::BigInt;
+use num_traits::{Euclid, ToPrimitive};
+use std::collections::HashSet;
+use std::convert::TryFrom;
+use std::fmt;
+use std::hash::Hash;
+use std::ops::{Add, Mul, Sub};
+
+// Integer types the analysis can run on.  Frequencies and change counts both use the same type,
+// since the number of changes before a repeat can be as large as the frequencies themselves.
+pub trait Frequency:
+    Clone + Ord + Hash + fmt::Display + Add<Output = Self> + Sub<Output = Self> + Mul<Output = Self>
+{
+    fn from_count(count: usize) -> Self;
+    fn to_count(&self) -> Option<usize>;
+    // Quotient and remainder where the remainder is never negative
+    fn div_rem_euclid(&self, other: &Self) -> (Self, Self);
+}
+
+// Wide enough that sums and change counts can't overflow for lists of up to `MAX_I128_CHANGES`
+// 64 bit changes
+impl Frequency for i128 {
+    fn from_count(count: usize) -> i128 {
+        return count as i128;
+    }
+
+    fn to_count(&self) -> Option<usize> {
+        return usize::try_from(*self).ok();
+    }
+
+    fn div_rem_euclid(&self, other: &i128) -> (i128, i128) {
+        return (
+            i128::div_euclid(*self, *other),
+            i128::rem_euclid(*self, *other),
+        );
+    }
+}
+
+// Sums stay below 2^91, so the differences between them and the number of passes they allow
+// stay below 2^92, and the number of changes those passes take stays below 2^120
+pub const MAX_I128_CHANGES: usize = 1 << 28;
+
+impl Frequency for BigInt {
+    fn from_count(count: usize) -> BigInt {
+        return BigInt::from(count);
+    }
+
+    fn to_count(&self) -> Option<usize> {
+        return ToPrimitive::to_usize(self);
+    }
+
+    fn div_rem_euclid(&self, other: &BigInt) -> (BigInt, BigInt) {
+        return (
+            Euclid::div_euclid(self, other),
+            Euclid::rem_euclid(self, other),
+        );
+    }
+}
+
+// Frequency reached after each change of the first pass, starting from 0 before any change.  Every
+// later pass visits the same values shifted by the drift of one whole pass.
+pub fn prefix_sums<T: Frequency>(values: &[T]) -> Vec<T> {
+    let mut sums = Vec::with_capacity(values.len());
+    let mut freq = T::from_count(0);
+    for v in values {
+        sums.push(freq.clone());
+        freq = freq + v.clone();
+    }
+    return sums;
+}
+
+// Number of changes applied when a frequency is first reached twice, and that frequency.
+//
+// The frequency after `k` whole passes and `j` more changes is `sums[j] + k * drift`, reached
+// after `k * n + j` changes.  Repeats within the first pass are found directly.  After that, a
+// value can only come back round to one from the first pass that differs from it by a multiple
+// of the drift, on the side the drift moves towards, so grouping the first pass by remainder
+// modulo the drift and sorting each group gives the nearest such value for every starting point.
+pub fn first_repeat<T: Frequency>(values: &[T]) -> Option<(T, T)> {
+    let n = values.len();
+    let zero = T::from_count(0);
+    let sums = prefix_sums(values);
+    let drift = values.iter().cloned().fold(zero.clone(), |a, b| a + b);
+
+    let mut seen = HashSet::new();
+    for (j, freq) in sums.iter().enumerate() {
+        if !seen.insert(freq) {
+            return Some((T::from_count(j), freq.clone()));
+        }
+    }
+    if drift == zero {
+        // Back to the start after one pass
+        return if n == 0 {
+            None
+        } else {
+            Some((T::from_count(n), zero))
+        };
+    }
+
+    let mut order: Vec<(T, usize)> = sums
+        .iter()
+        .enumerate()
+        .map(|(j, freq)| (freq.div_rem_euclid(&drift).1, j))
+        .collect();
+    order.sort_by(|a, b| a.0.cmp(&b.0).then_with(|| sums[a.1].cmp(&sums[b.1])));
+
+    let mut best: Option<(T, T)> = None;
+    for pair in order.windows(2) {
+        if pair[0].0 != pair[1].0 {
+            continue;
+        }
+        // Every value in the first pass is different, so the group is strictly increasing and
+        // values move up it when the drift is positive and down it otherwise
+        let (from, to) = if drift > zero {
+            (pair[0].1, pair[1].1)
+        } else {
+            (pair[1].1, pair[0].1)
+        };
+        let passes = (sums[to].clone() - sums[from].clone())
+            .div_rem_euclid(&drift)
+            .0;
+        let step = passes * T::from_count(n) + T::from_count(from);
+        if best.as_ref().is_none_or(|(best_step, _)| step < *best_step) {
+            best = Some((step, sums[to].clone()));
+        }
+    }
+    return best;
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Repeat<T> {
+    pub frequency: T,
+    // Changes applied when it's reached for the second time
+    pub steps: T,
+    // Pass through the change list it happens in, counting from 1
+    pub pass: T,
+    // Position in the change list of the change that reaches it
+    pub index: usize,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FrequencyAnalysis<T> {
+    pub num_changes: usize,
+    // Change over one whole pass, which is also the part 1 answer
+    pub drift: T,
+    // Lowest and highest frequencies reached up to the first repeat, or during the first pass
+    // if nothing repeats
+    pub min_frequency: T,
+    pub max_frequency: T,
+    pub first_repeat: Option<Repeat<T>>,
+}
+
+impl<T: Frequency> FrequencyAnalysis<T> {
+    pub fn new(values: &[T]) -> FrequencyAnalysis<T> {
+        let n = values.len();
+        let zero = T::from_count(0);
+        let one = T::from_count(1);
+        let count = T::from_count(n);
+        let sums = prefix_sums(values);
+        let drift = values.iter().cloned().fold(zero.clone(), |a, b| a + b);
+        let first_repeat = first_repeat(values).map(|(steps, frequency)| {
+            let (passes, index) = (steps.clone() - one.clone()).div_rem_euclid(&count);
+            Repeat {
+                frequency,
+                steps,
+                pass: passes + one.clone(),
+                index: index.to_count().unwrap(),
+            }
+        });
+
+        // The frequency after `j` changes of each pass moves by the drift every time round, so
+        // its extremes are at the first and last pass that reaches it.  Frequency 0 at the start
+        // is always reached.
+        let last_step = first_repeat
+            .as_ref()
+            .map_or(count.clone(), |repeat| repeat.steps.clone());
+        let (mut min_frequency, mut max_frequency) = (zero.clone(), zero);
+        for (j, freq) in sums.iter().enumerate() {
+            let j = T::from_count(j);
+            if j > last_step {
+                break;
+            }
+            let passes = (last_step.clone() - j).div_rem_euclid(&count).0;
+            let last = freq.clone() + passes * drift.clone();
+            for reached in [freq.clone(), last].iter() {
+                if *reached < min_frequency {
+                    min_frequency = reached.clone();
+                }
+                if *reached > max_frequency {
+                    max_frequency = reached.clone();
+                }
+            }
+        }
+
+        return FrequencyAnalysis {
+            num_changes: n,
+            drift,
+            min_frequency,
+            max_frequency,
+            first_repeat,
+        };
+    }
+}
+
+impl<T: fmt::Display> fmt::Display for FrequencyAnalysis<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "changes: {}", self.num_changes)?;
+        writeln!(f, "drift per pass: {}", self.drift)?;
+        writeln!(f, "lowest frequency: {}", self.min_frequency)?;
+        writeln!(f, "highest frequency: {}", self.max_frequency)?;
+        match &self.first_repeat {
+            Some(repeat) => {
+                writeln!(f, "first repeat: {}", repeat.frequency)?;
+                writeln!(f, "changes applied: {}", repeat.steps)?;
+                writeln!(f, "passes: {}", repeat.pass)?;
+                write!(f, "at change: {} (line {})", repeat.index, repeat.index + 1)
+            }
+            None => write!(f, "first repeat: none"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn puzzle_example() {
+        let values: Vec<i128> = vec![1, -2, 3, 1];
+        assert_eq!(prefix_sums(&values), vec![0, 1, -1, 2]);
+        assert_eq!(first_repeat(&values), Some((6, 2)));
+        assert_eq!(
+            FrequencyAnalysis::new(&values),
+            FrequencyAnalysis {
+                num_changes: 4,
+                drift: 3,
+                min_frequency: -1,
+                max_frequency: 4,
+                first_repeat: Some(Repeat {
+                    frequency: 2,
+                    steps: 6,
+                    pass: 2,
+                    index: 1,
+                }),
+            }
+        );
+    }
+
+    #[test]
+    fn repeats_within_and_after_passes() {
+        // Back to 0 within the first pass
+        let analysis = FrequencyAnalysis::new(&[3i128, -3, 5]);
+        assert_eq!(
+            analysis.first_repeat,
+            Some(Repeat {
+                frequency: 0,
+                steps: 2,
+                pass: 1,
+                index: 1,
+            })
+        );
+        assert_eq!((analysis.min_frequency, analysis.max_frequency), (0, 3));
+        // Back to 0 at the end of the first pass
+        let analysis = FrequencyAnalysis::new(&[1i128, -1]);
+        assert_eq!(
+            analysis
+                .first_repeat
+                .map(|r| (r.frequency, r.steps, r.pass, r.index)),
+            Some((0, 2, 1, 1))
+        );
+        // Coming back round to 14 takes 13 changes
+        let analysis = FrequencyAnalysis::new(&[7i128, 7, -2, -7, -4]);
+        assert_eq!(analysis.drift, 1);
+        assert_eq!(
+            analysis
+                .first_repeat
+                .map(|r| (r.frequency, r.steps, r.pass, r.index)),
+            Some((14, 13, 3, 2))
+        );
+        assert_eq!((analysis.min_frequency, analysis.max_frequency), (0, 16));
+    }
+
+    #[test]
+    fn no_repeat() {
+        let analysis = FrequencyAnalysis::new(&[2i128, 2]);
+        assert_eq!(analysis.first_repeat, None);
+        assert_eq!((analysis.min_frequency, analysis.max_frequency), (0, 4));
+        assert_eq!(FrequencyAnalysis::<i128>::new(&[]).first_repeat, None);
+    }
+}
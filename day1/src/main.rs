@@ -1,6 +1,99 @@
+#![allow(clippy::needless_return)]
+
 use std::collections::HashSet;
+use std::time::Instant;
+
+mod analysis;
+
+use analysis::{Frequency, FrequencyAnalysis};
+use num_bigint::BigInt;
+use parselib::GenericError;
+use std::convert::TryFrom;
 
-fn part2(values: &[i64]) -> i64 {
+// Reads one change per line.  Changes too big for 64 bits are reported as such rather than as
+// just being invalid, since the arbitrary precision mode can handle them.
+fn parse_changes(input: &str) -> Result<Vec<i64>, GenericError> {
+    let mut result = Vec::new();
+    for (i, line) in input.lines().enumerate() {
+        let line = line.trim();
+        match line.parse() {
+            Ok(v) => result.push(v),
+            Err(_) if line.parse::<BigInt>().is_ok() => {
+                return Err(GenericError::new(&format!(
+                    "line {}: {} doesn't fit in 64 bits (try --big)",
+                    i + 1,
+                    line
+                )))
+            }
+            Err(_) => return Err(invalid_change(i, line)),
+        }
+    }
+    return Ok(result);
+}
+
+fn parse_big_changes(input: &str) -> Result<Vec<BigInt>, GenericError> {
+    let mut result = Vec::new();
+    for (i, line) in input.lines().enumerate() {
+        let line = line.trim();
+        result.push(line.parse().map_err(|_| invalid_change(i, line))?);
+    }
+    return Ok(result);
+}
+
+fn invalid_change(line_index: usize, line: &str) -> GenericError {
+    return GenericError::new(&format!(
+        "line {}: invalid change {:?}",
+        line_index + 1,
+        line
+    ));
+}
+
+fn part1(values: &[i64]) -> Result<i64, GenericError> {
+    let mut freq: i64 = 0;
+    for (i, v) in values.iter().enumerate() {
+        freq = freq.checked_add(*v).ok_or_else(|| {
+            GenericError::new(&format!(
+                "frequency overflows 64 bits at line {} (try --big)",
+                i + 1
+            ))
+        })?;
+    }
+    return Ok(freq);
+}
+
+// Runs the analysis with enough headroom that nothing can overflow, then checks that every
+// frequency reached up to the first repeat fits in 64 bits.  The number of changes before the
+// repeat can be larger.
+fn analyze(values: &[i64]) -> Result<FrequencyAnalysis<i128>, GenericError> {
+    if values.len() > analysis::MAX_I128_CHANGES {
+        return Err(GenericError::new(&format!(
+            "more than {} changes (try --big)",
+            analysis::MAX_I128_CHANGES
+        )));
+    }
+    let wide: Vec<i128> = values.iter().map(|&v| v as i128).collect();
+    let result = FrequencyAnalysis::new(&wide);
+    for &frequency in &[result.min_frequency, result.max_frequency] {
+        if i64::try_from(frequency).is_err() {
+            return Err(GenericError::new(&format!(
+                "frequency reaches {} before the first repeat, which doesn't fit in 64 bits (try --big)",
+                frequency
+            )));
+        }
+    }
+    return Ok(result);
+}
+
+// First frequency reached twice, or None if every frequency is different
+fn part2(values: &[i64]) -> Result<Option<i64>, GenericError> {
+    return Ok(analyze(values)?
+        .first_repeat
+        .map(|repeat| repeat.frequency as i64));
+}
+
+// Applies the changes one at a time until a frequency comes up again.  Never finishes if there
+// is no repeat.
+fn part2_naive(values: &[i64]) -> i64 {
     let mut seen_values = HashSet::new();
     let mut freq = 0;
     seen_values.insert(freq);
@@ -13,8 +106,203 @@ fn part2(values: &[i64]) -> i64 {
     panic!("infinite loop");
 }
 
-fn main() {
-    let input = parselib::parse_lines::<i64, _>("input.txt").expect("Parse error");
-    println!("part 1: {}", input.iter().sum::<i64>());
-    println!("part 2: {}", part2(input.as_slice()));
+// Changes whose frequencies within a pass are all different values in -spread..=spread, with a
+// whole pass drifting by exactly `drift`, so the first repeat needs many passes
+fn random_changes(count: usize, spread: i64, drift: i64, seed: u64) -> Vec<i64> {
+    let mut random = parselib::Random::new(seed);
+    let mut next_random = || random.range(-spread, spread);
+    let mut seen = HashSet::new();
+    let mut sums = vec![0];
+    seen.insert(0);
+    while sums.len() < count {
+        let freq = next_random();
+        if seen.insert(freq) {
+            sums.push(freq);
+        }
+    }
+    sums.push(drift);
+    return sums.windows(2).map(|pair| pair[1] - pair[0]).collect();
+}
+
+fn bench() {
+    for &(count, spread, drift) in &[
+        (100, 100_000_000, 1),
+        (1000, 1_000_000_000, 1),
+        (1000, 3_000_000_000, 3),
+        (10_000, 10_000_000_000, 1),
+    ] {
+        let changes = random_changes(count, spread, drift, count as u64 + spread as u64);
+
+        let start = Instant::now();
+        let fast = part2(&changes).unwrap();
+        let fast_time = start.elapsed();
+
+        let start = Instant::now();
+        let naive = part2_naive(&changes);
+        let naive_time = start.elapsed();
+
+        assert!(fast == Some(naive));
+        println!(
+            "{:>6} changes in ±{:<11} drift {}: {}",
+            count,
+            spread,
+            drift,
+            parselib::compare_times("cycle math", fast_time, "naive", naive_time)
+        );
+    }
+}
+
+fn print_answers<T: Frequency>(analysis: &FrequencyAnalysis<T>) {
+    println!("part 1: {}", analysis.drift);
+    match &analysis.first_repeat {
+        Some(repeat) => println!("part 2: {}", repeat.frequency),
+        None => println!("part 2: no frequency is reached twice"),
+    }
+}
+
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let mut report = false;
+    let mut big = false;
+    for arg in args.iter() {
+        match arg.as_str() {
+            "bench" => {
+                bench();
+                return Ok(());
+            }
+            "report" => report = true,
+            "--big" => big = true,
+            _ => {
+                return Err(Box::new(GenericError::new(&format!(
+                    "unknown option {}",
+                    arg
+                ))))
+            }
+        }
+    }
+
+    let input = parselib::load_text_file("input.txt")?;
+    if big {
+        let analysis = FrequencyAnalysis::new(&parse_big_changes(&input)?);
+        if report {
+            println!("{}", analysis);
+        } else {
+            print_answers(&analysis);
+        }
+        return Ok(());
+    }
+
+    let changes = parse_changes(&input)?;
+    if report {
+        println!("{}", analyze(&changes)?);
+        return Ok(());
+    }
+    println!("part 1: {}", part1(&changes)?);
+    match part2(&changes)? {
+        Some(freq) => println!("part 2: {}", freq),
+        None => println!("part 2: no frequency is reached twice"),
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn big_analysis(input: &str) -> FrequencyAnalysis<BigInt> {
+        return FrequencyAnalysis::new(&parse_big_changes(input).unwrap());
+    }
+
+    #[test]
+    fn puzzle_examples() {
+        for &(input, sum, repeat) in &[
+            ("+1\n-2\n+3\n+1\n", 3, 2),
+            ("+1\n-1\n", 0, 0),
+            ("+3\n+3\n+4\n-2\n-4\n", 4, 10),
+            ("-6\n+3\n+8\n+5\n-6\n", 4, 5),
+            ("+7\n+7\n-2\n-7\n-4\n", 1, 14),
+        ] {
+            let changes = parse_changes(input).unwrap();
+            assert_eq!(part1(&changes).unwrap(), sum);
+            assert_eq!(part2(&changes).unwrap(), Some(repeat));
+        }
+    }
+
+    #[test]
+    fn no_repeat() {
+        assert_eq!(part2(&[1, 2]).unwrap(), None);
+        assert_eq!(part2(&[]).unwrap(), None);
+    }
+
+    // Small random change lists, checked against applying the changes one at a time.  Nothing
+    // repeats exactly when the drift is nonzero and no two frequencies of the first pass are the
+    // same modulo it, which is the only case the naive search can't be run on.
+    #[test]
+    fn matches_naive_search() {
+        let mut random = parselib::Random::new(45);
+        for _ in 0..2000 {
+            let count = 1 + random.below(8) as usize;
+            let changes: Vec<i64> = (0..count).map(|_| random.range(-10, 10)).collect();
+            let drift: i64 = changes.iter().sum();
+            match part2(&changes).unwrap() {
+                Some(repeat) => assert_eq!(repeat, part2_naive(&changes), "{:?}", changes),
+                None => {
+                    assert_ne!(drift, 0, "{:?}", changes);
+                    let wide: Vec<i128> = changes.iter().map(|&v| v as i128).collect();
+                    let remainders: HashSet<i128> = analysis::prefix_sums(&wide)
+                        .iter()
+                        .map(|s| s.rem_euclid(drift as i128))
+                        .collect();
+                    assert_eq!(remainders.len(), count, "{:?}", changes);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn rejects_changes_wider_than_64_bits() {
+        let error = parse_changes("+1\n+9223372036854775808\n").unwrap_err();
+        assert!(error.to_string().contains("line 2"));
+        assert!(error.to_string().contains("64 bits"));
+        assert!(parse_changes("+1\nfive\n").is_err());
+    }
+
+    #[test]
+    fn part1_overflow_is_an_error() {
+        let error = part1(&[i64::MAX, 1]).unwrap_err();
+        assert!(error.to_string().contains("line 2"));
+        assert_eq!(part1(&[i64::MAX, -1, 1]).unwrap(), i64::MAX);
+    }
+
+    #[test]
+    fn part2_overflow_is_an_error() {
+        // The first repeat is i64::MAX, but other frequencies pass it on the way
+        let changes = [i64::MAX, -i64::MAX + 1];
+        assert!(part2(&changes).is_err());
+        let big = big_analysis("+9223372036854775807\n-9223372036854775806\n");
+        assert_eq!(big.first_repeat.unwrap().frequency, BigInt::from(i64::MAX));
+    }
+
+    #[test]
+    fn big_mode_matches_64_bit_mode() {
+        for input in &[
+            "+1\n-2\n+3\n+1\n",
+            "+3\n+3\n+4\n-2\n-4\n",
+            "-6\n+3\n+8\n+5\n-6\n",
+            "+1\n+2\n",
+            "-5\n+2\n-1\n",
+            "+4611686018427387904\n-4611686018427387903\n",
+        ] {
+            let wide = analyze(&parse_changes(input).unwrap()).unwrap();
+            assert_eq!(big_analysis(input).to_string(), wide.to_string());
+        }
+    }
+
+    #[test]
+    fn big_mode_handles_values_beyond_64_bits() {
+        let analysis = big_analysis("+99999999999999999999\n+1\n");
+        assert_eq!(analysis.drift, "100000000000000000000".parse().unwrap());
+        assert_eq!(analysis.max_frequency, analysis.drift);
+        assert_eq!(analysis.first_repeat, None);
+    }
 }
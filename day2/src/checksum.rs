@@ -0,0 +1,214 @@
+use super::ids::Alphabet;
+use parselib::GenericError;
+use std::collections::BTreeMap;
+use std::fmt;
+use std::str::FromStr;
+
+// Letter frequency analysis for box IDs, generalising the part 1 count of IDs with a letter
+// exactly twice or three times to any number of times
+
+// Counts how many times each symbol appears in an ID into `num_seen`, which is indexed by symbol
+// and starts out all zero, returning each different symbol once.  The caller resets the counts
+// for those symbols afterwards, so the same table serves every ID however big the alphabet.
+pub fn letter_counts(id: &[u32], num_seen: &mut [usize]) -> Vec<u32> {
+    let mut symbols = Vec::new();
+    for &v in id.iter() {
+        if num_seen[v as usize] == 0 {
+            symbols.push(v);
+        }
+        num_seen[v as usize] += 1;
+    }
+    return symbols;
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct LetterHistogram {
+    // For each number of repeats, how many IDs have some letter exactly that many times
+    ids_with_count: BTreeMap<usize, usize>,
+    // For each number of repeats, how many IDs have each letter exactly that many times
+    letters_with_count: BTreeMap<usize, BTreeMap<String, usize>>,
+}
+
+impl LetterHistogram {
+    pub fn from_ids(ids: &[&[u32]], alphabet: &Alphabet) -> LetterHistogram {
+        let mut result = LetterHistogram::default();
+        let mut num_seen = vec![0usize; alphabet.len()];
+        for id in ids {
+            let mut counts: Vec<usize> = Vec::new();
+            for symbol in letter_counts(id, &mut num_seen) {
+                let count = num_seen[symbol as usize];
+                num_seen[symbol as usize] = 0;
+                *result
+                    .letters_with_count
+                    .entry(count)
+                    .or_default()
+                    .entry(alphabet.unit(symbol).to_string())
+                    .or_default() += 1;
+                counts.push(count);
+            }
+            counts.sort_unstable();
+            counts.dedup();
+            for count in counts {
+                *result.ids_with_count.entry(count).or_default() += 1;
+            }
+        }
+        return result;
+    }
+
+    pub fn ids_with_count(&self, count: usize) -> usize {
+        return *self.ids_with_count.get(&count).unwrap_or(&0);
+    }
+
+    // Letters appearing exactly `count` times in some ID, with how many IDs they do that in
+    pub fn letters_with_count(&self, count: usize) -> Vec<(&str, usize)> {
+        return match self.letters_with_count.get(&count) {
+            Some(letters) => letters.iter().map(|(l, &n)| (l.as_str(), n)).collect(),
+            None => Vec::new(),
+        };
+    }
+
+    // Every number of repeats that some ID has a letter for
+    pub fn counts(&self) -> impl Iterator<Item = usize> + '_ {
+        return self.ids_with_count.keys().cloned();
+    }
+
+    pub fn checksum(&self, formula: &Formula) -> usize {
+        let terms = formula.counts.iter().map(|&k| self.ids_with_count(k));
+        return match formula.combine {
+            Combine::Product => terms.product(),
+            Combine::Sum => terms.sum(),
+        };
+    }
+}
+
+impl fmt::Display for LetterHistogram {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for count in self.counts() {
+            let letters: Vec<String> = self
+                .letters_with_count(count)
+                .iter()
+                .map(|(letter, n)| format!("{}:{}", letter, n))
+                .collect();
+            writeln!(
+                f,
+                "exactly {}: {} IDs, letters {}",
+                count,
+                self.ids_with_count(count),
+                letters.join(" ")
+            )?;
+        }
+        return Ok(());
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Combine {
+    Product,
+    Sum,
+}
+
+// Which numbers of repeats go into the checksum and how they're combined, written like "2*3"
+// for the puzzle's checksum or "2+3+4"
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Formula {
+    pub counts: Vec<usize>,
+    pub combine: Combine,
+}
+
+impl Default for Formula {
+    fn default() -> Formula {
+        return Formula {
+            counts: vec![2, 3],
+            combine: Combine::Product,
+        };
+    }
+}
+
+impl FromStr for Formula {
+    type Err = GenericError;
+
+    fn from_str(s: &str) -> Result<Formula, GenericError> {
+        let combine = match (s.contains('*'), s.contains('+')) {
+            (true, true) => {
+                return Err(GenericError::new(&format!("formula {:?} mixes * and +", s)))
+            }
+            (_, true) => Combine::Sum,
+            _ => Combine::Product,
+        };
+        let counts = s
+            .split(['*', '+'])
+            .map(|term| term.trim().parse())
+            .collect::<Result<Vec<usize>, _>>()
+            .map_err(|_| GenericError::new(&format!("invalid formula {:?}", s)))?;
+        return Ok(Formula { counts, combine });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ids::BoxIds;
+    use crate::unicode::Segmentation;
+
+    fn formula(s: &str) -> Result<Formula, String> {
+        return s.parse().map_err(|e: GenericError| e.to_string());
+    }
+
+    #[test]
+    fn puzzle_example() {
+        let ids = BoxIds::parse(
+            b"abcdef\nbababc\nabbcde\nabcccd\naabcdd\nabcdee\nababab\n",
+            Segmentation::Scalars,
+        )
+        .unwrap();
+        let histogram = LetterHistogram::from_ids(&ids.ids(), &ids.alphabet);
+        assert_eq!(histogram.counts().collect::<Vec<_>>(), vec![1, 2, 3]);
+        assert_eq!(histogram.ids_with_count(2), 4);
+        assert_eq!(histogram.ids_with_count(3), 3);
+        assert_eq!(histogram.ids_with_count(4), 0);
+        assert_eq!(
+            histogram.letters_with_count(2),
+            vec![("a", 2), ("b", 1), ("d", 1), ("e", 1)]
+        );
+        assert_eq!(
+            histogram.letters_with_count(3),
+            vec![("a", 1), ("b", 2), ("c", 1)]
+        );
+        assert!(histogram.letters_with_count(4).is_empty());
+        assert_eq!(histogram.checksum(&Formula::default()), 12);
+        assert_eq!(histogram.checksum(&formula("2+3").unwrap()), 7);
+        assert_eq!(histogram.checksum(&formula("2*3*4").unwrap()), 0);
+    }
+
+    #[test]
+    fn parse_formulas() {
+        assert_eq!(formula("2*3"), Ok(Formula::default()));
+        assert_eq!(
+            formula("2+3+4"),
+            Ok(Formula {
+                counts: vec![2, 3, 4],
+                combine: Combine::Sum
+            })
+        );
+        assert_eq!(
+            formula(" 5 "),
+            Ok(Formula {
+                counts: vec![5],
+                combine: Combine::Product
+            })
+        );
+        assert_eq!(
+            formula("2*3+4"),
+            Err("Error: formula \"2*3+4\" mixes * and +".to_string())
+        );
+        assert_eq!(formula(""), Err("Error: invalid formula \"\"".to_string()));
+        assert_eq!(
+            formula("2**3"),
+            Err("Error: invalid formula \"2**3\"".to_string())
+        );
+        assert_eq!(
+            formula("two"),
+            Err("Error: invalid formula \"two\"".to_string())
+        );
+    }
+}
@@ -0,0 +1,197 @@
+use std::collections::HashMap;
+
+// Searching box IDs, as symbols, for ones that differ in only a few positions, without comparing every pair
+
+pub fn hamming_distance(a: &[u32], b: &[u32]) -> usize {
+    return a.iter().zip(b.iter()).filter(|(x, y)| x != y).count();
+}
+
+const HASH_BASE: u64 = 0x100_0000_01b3;
+
+// Indices of the first pair of IDs, in file order, that differ in exactly one position, along
+// with that position.
+//
+// Every ID is hashed once, and the hash of it with each position masked out is then found in
+// constant time by taking that character's contribution back out.  IDs that differ in only that
+// position share the masked hash, so bucketing by it finds every such pair in O(n * L) expected
+// time, checking the actual symbols to rule out hash collisions.
+pub fn find_one_apart(ids: &[&[u32]]) -> Option<(usize, usize, usize)> {
+    let width = ids.first().map_or(0, |id| id.len());
+    // Weight of each position in the hash
+    let mut powers = vec![1u64; width];
+    for i in (0..width.saturating_sub(1)).rev() {
+        powers[i] = powers[i + 1].wrapping_mul(HASH_BASE);
+    }
+    let hashes: Vec<u64> = ids
+        .iter()
+        .map(|id| {
+            id.iter().zip(powers.iter()).fold(0u64, |h, (&c, &p)| {
+                h.wrapping_add((c as u64).wrapping_mul(p))
+            })
+        })
+        .collect();
+
+    let mut best: Option<(usize, usize, usize)> = None;
+    let mut buckets: HashMap<u64, Vec<usize>> = HashMap::new();
+    for position in 0..width {
+        buckets.clear();
+        for (b, id) in ids.iter().enumerate() {
+            let key = hashes[b].wrapping_sub((id[position] as u64).wrapping_mul(powers[position]));
+            let bucket = buckets.entry(key).or_default();
+            let matched = bucket.iter().find(|&&a| {
+                ids[a][position] != id[position]
+                    && ids[a][..position] == id[..position]
+                    && ids[a][position + 1..] == id[position + 1..]
+            });
+            if let Some(&a) = matched {
+                if best.is_none_or(|(best_a, best_b, _)| (a, b) < (best_a, best_b)) {
+                    best = Some((a, b, position));
+                }
+            } else if !bucket.iter().any(|&a| ids[a] == *id) {
+                // Later IDs only need the first of any exact duplicates
+                bucket.push(b);
+            }
+        }
+    }
+    return best;
+}
+
+// Finds every ID within a Hamming distance of a query, up to the distance the index was built
+// for.  IDs are split into one more block than that distance, so by the pigeonhole principle
+// any ID close enough to the query matches it exactly in at least one block, and only IDs that
+// do need comparing in full.
+pub struct HammingIndex<'a> {
+    ids: &'a [&'a [u32]],
+    max_distance: usize,
+    // Start of each block, followed by the end of the last one
+    block_starts: Vec<usize>,
+    blocks: Vec<HashMap<&'a [u32], Vec<usize>>>,
+}
+
+impl<'a> HammingIndex<'a> {
+    pub fn new(ids: &'a [&'a [u32]], max_distance: usize) -> HammingIndex<'a> {
+        let width = ids.first().map_or(0, |id| id.len());
+        let num_blocks = max_distance + 1;
+        let block_starts: Vec<usize> = (0..=num_blocks).map(|b| b * width / num_blocks).collect();
+        let mut blocks = vec![HashMap::new(); num_blocks];
+        for (i, id) in ids.iter().enumerate() {
+            for (b, block) in blocks.iter_mut().enumerate() {
+                let key = &id[block_starts[b]..block_starts[b + 1]];
+                block.entry(key).or_insert_with(Vec::new).push(i);
+            }
+        }
+        return HammingIndex {
+            ids,
+            max_distance,
+            block_starts,
+            blocks,
+        };
+    }
+
+    // Indices and distances of every ID within `distance` of `query`, in file order.  None if
+    // the query is the wrong length or the distance is more than the index supports.
+    pub fn within(&self, query: &[u32], distance: usize) -> Option<Vec<(usize, usize)>> {
+        let width = self.block_starts.last().unwrap();
+        if distance > self.max_distance || (!self.ids.is_empty() && query.len() != *width) {
+            return None;
+        }
+        let mut candidates: Vec<usize> = Vec::new();
+        for (b, block) in self.blocks.iter().enumerate() {
+            let key = &query[self.block_starts[b]..self.block_starts[b + 1]];
+            if let Some(matches) = block.get(key) {
+                candidates.extend_from_slice(matches);
+            }
+        }
+        candidates.sort_unstable();
+        candidates.dedup();
+        return Some(
+            candidates
+                .into_iter()
+                .map(|i| (i, hamming_distance(self.ids[i], query)))
+                .filter(|&(_, d)| d <= distance)
+                .collect(),
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn random_ids(count: usize, width: usize, seed: u64) -> Vec<Vec<u32>> {
+        let mut random = parselib::Random::new(seed);
+        return (0..count)
+            .map(|_| (0..width).map(|_| random.below(3) as u32).collect())
+            .collect();
+    }
+
+    fn within_brute_force(ids: &[&[u32]], query: &[u32], distance: usize) -> Vec<(usize, usize)> {
+        return ids
+            .iter()
+            .enumerate()
+            .map(|(i, id)| (i, hamming_distance(id, query)))
+            .filter(|&(_, d)| d <= distance)
+            .collect();
+    }
+
+    #[test]
+    fn within_matches_brute_force() {
+        for seed in 1..20 {
+            let width = 1 + seed as usize % 7;
+            let owned = random_ids(30, width, seed);
+            let ids: Vec<&[u32]> = owned.iter().map(|id| id.as_slice()).collect();
+            let queries = random_ids(10, width, seed + 100);
+            // Distances of at least the width leave some blocks empty, so every ID is a candidate
+            for &max_distance in [0, 1, 2, width, width + 3].iter() {
+                let index = HammingIndex::new(&ids, max_distance);
+                for query in queries.iter().chain(owned.iter().take(3)) {
+                    for distance in 0..=max_distance {
+                        assert_eq!(
+                            index.within(query, distance).unwrap(),
+                            within_brute_force(&ids, query, distance),
+                            "{:?} {:?} {}",
+                            ids,
+                            query,
+                            distance
+                        );
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn within_rejects_what_the_index_cant_answer() {
+        let ids: Vec<&[u32]> = vec![&[1, 2, 3], &[1, 2, 4]];
+        let index = HammingIndex::new(&ids, 1);
+        assert_eq!(index.within(&[1, 2], 1), None);
+        assert_eq!(index.within(&[1, 2, 3], 2), None);
+        assert_eq!(index.within(&[1, 2, 3], 0), Some(vec![(0, 0)]));
+        assert!(HammingIndex::new(&[], 2)
+            .within(&[1], 2)
+            .unwrap()
+            .is_empty());
+    }
+
+    #[test]
+    fn one_apart_matches_brute_force() {
+        for seed in 1..40 {
+            let owned = random_ids(12, 1 + seed as usize % 5, seed);
+            let ids: Vec<&[u32]> = owned.iter().map(|id| id.as_slice()).collect();
+            let mut expected = None;
+            'search: for a in 0..ids.len() {
+                for b in a + 1..ids.len() {
+                    if hamming_distance(ids[a], ids[b]) == 1 {
+                        expected = Some((a, b));
+                        break 'search;
+                    }
+                }
+            }
+            let found = find_one_apart(&ids);
+            assert_eq!(found.map(|(a, b, _)| (a, b)), expected, "{:?}", ids);
+            if let Some((a, _, position)) = found {
+                assert_ne!(ids[a][position], ids[expected.unwrap().1][position]);
+            }
+        }
+    }
+}
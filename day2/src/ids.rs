@@ -0,0 +1,242 @@
+use super::unicode::Segmentation;
+use parselib::GenericError;
+use std::collections::HashMap;
+
+// Box IDs stored as symbols, so that comparing and counting works the same whether the units of
+// an ID are bytes, Unicode scalar values or grapheme clusters.  ASCII characters are their own
+// symbols, and anything else is numbered in the order it's first seen.
+
+#[derive(Debug, Clone)]
+pub struct Alphabet {
+    units: Vec<String>,
+    symbols: HashMap<String, u32>,
+}
+
+const ASCII_SYMBOLS: u32 = 128;
+
+impl Alphabet {
+    pub fn new() -> Alphabet {
+        let units: Vec<String> = (0..ASCII_SYMBOLS as u8)
+            .map(|c| (c as char).to_string())
+            .collect();
+        return Alphabet {
+            units,
+            symbols: HashMap::new(),
+        };
+    }
+
+    pub fn len(&self) -> usize {
+        return self.units.len();
+    }
+
+    pub fn symbol(&self, unit: &str) -> Option<u32> {
+        if unit.len() == 1 && unit.is_ascii() {
+            return Some(unit.as_bytes()[0] as u32);
+        }
+        return self.symbols.get(unit).cloned();
+    }
+
+    pub fn intern(&mut self, unit: &str) -> u32 {
+        if let Some(symbol) = self.symbol(unit) {
+            return symbol;
+        }
+        let symbol = self.units.len() as u32;
+        self.units.push(unit.to_string());
+        self.symbols.insert(unit.to_string(), symbol);
+        return symbol;
+    }
+
+    pub fn unit(&self, symbol: u32) -> &str {
+        return &self.units[symbol as usize];
+    }
+
+    pub fn text(&self, symbols: &[u32]) -> String {
+        return symbols.iter().map(|&s| self.unit(s)).collect();
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct BoxIds {
+    symbols: Vec<u32>,
+    count: usize,
+    width: usize,
+    pub alphabet: Alphabet,
+    pub segmentation: Segmentation,
+}
+
+// Units can be anything other than whitespace and control characters
+fn is_id_unit(unit: &str) -> bool {
+    return unit
+        .chars()
+        .next()
+        .is_some_and(|c| !c.is_whitespace() && !c.is_control());
+}
+
+// The common case of an ASCII file where every line is the same width and ends in a unix
+// newline, including the last.  Each ID is then a fixed size chunk of bytes without needing to
+// search for line ends or decode anything.
+fn split_fixed_width(input: &[u8]) -> Option<Vec<&[u8]>> {
+    let width = input.iter().position(|&c| c == b'\n')?;
+    if !input.len().is_multiple_of(width + 1)
+        || !input.is_ascii()
+        || input.contains(&b'\r')
+        || !input
+            .iter()
+            .skip(width)
+            .step_by(width + 1)
+            .all(|&c| c == b'\n')
+    {
+        return None;
+    }
+    return Some(input.chunks(width + 1).map(|line| &line[..width]).collect());
+}
+
+// Any mix of unix and windows line endings, with or without a newline at the end
+fn split_lines(input: &str) -> Vec<&str> {
+    let mut lines: Vec<&str> = input
+        .split('\n')
+        .map(|line| line.strip_suffix('\r').unwrap_or(line))
+        .collect();
+    if lines.last().is_some_and(|line| line.is_empty()) {
+        lines.pop();
+    }
+    return lines;
+}
+
+impl BoxIds {
+    pub fn parse(input: &[u8], segmentation: Segmentation) -> Result<BoxIds, GenericError> {
+        let mut result = BoxIds {
+            symbols: Vec::new(),
+            count: 0,
+            width: 0,
+            alphabet: Alphabet::new(),
+            segmentation,
+        };
+        if let Some(lines) = split_fixed_width(input) {
+            // ASCII is a single unit per byte however it's segmented
+            for (i, line) in lines.iter().enumerate() {
+                if let Some(column) = line.iter().position(|&c| !c.is_ascii_graphic()) {
+                    return Err(invalid_unit(i, column, &(line[column] as char).to_string()));
+                }
+                result.symbols.extend(line.iter().map(|&c| c as u32));
+            }
+            result.count = lines.len();
+            result.width = lines[0].len();
+            return Ok(result);
+        }
+
+        let input = std::str::from_utf8(input).map_err(|e| {
+            let line = input[..e.valid_up_to()]
+                .iter()
+                .filter(|&&c| c == b'\n')
+                .count();
+            GenericError::new(&format!("line {} is not valid UTF-8", line + 1))
+        })?;
+        for (i, line) in split_lines(input).iter().enumerate() {
+            let units = segmentation.split(line);
+            if i == 0 {
+                result.width = units.len();
+            } else if units.len() != result.width {
+                return Err(GenericError::new(&format!(
+                    "line {} has {} characters but line 1 has {}",
+                    i + 1,
+                    units.len(),
+                    result.width
+                )));
+            }
+            for (column, unit) in units.iter().enumerate() {
+                if !is_id_unit(unit) {
+                    return Err(invalid_unit(i, column, unit));
+                }
+                let symbol = result.alphabet.intern(unit);
+                result.symbols.push(symbol);
+            }
+            result.count += 1;
+        }
+        return Ok(result);
+    }
+
+    pub fn ids(&self) -> Vec<&[u32]> {
+        if self.width == 0 {
+            return vec![&[]; self.count];
+        }
+        return self.symbols.chunks(self.width).collect();
+    }
+
+    pub fn width(&self) -> usize {
+        return self.width;
+    }
+
+    // Splits something to compare against the IDs the same way they were split.  Units that
+    // don't appear in any ID get a symbol that won't match anything.
+    pub fn encode(&self, query: &str) -> Vec<u32> {
+        return self
+            .segmentation
+            .split(query)
+            .iter()
+            .map(|unit| self.alphabet.symbol(unit).unwrap_or(u32::MAX))
+            .collect();
+    }
+}
+
+fn invalid_unit(line_index: usize, column: usize, unit: &str) -> GenericError {
+    return GenericError::new(&format!(
+        "line {}: invalid character {:?} at column {}",
+        line_index + 1,
+        unit,
+        column + 1
+    ));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn texts(ids: &BoxIds) -> Vec<String> {
+        return ids.ids().iter().map(|id| ids.alphabet.text(id)).collect();
+    }
+
+    #[test]
+    fn fixed_width_ascii() {
+        let ids = BoxIds::parse(b"abc\nxyz\n", Segmentation::Scalars).unwrap();
+        assert_eq!(texts(&ids), vec!["abc", "xyz"]);
+        assert_eq!(ids.width(), 3);
+        assert_eq!(ids.ids()[0], &[b'a' as u32, b'b' as u32, b'c' as u32][..]);
+    }
+
+    #[test]
+    fn line_endings() {
+        for input in ["abc\r\nxyz\r\n", "abc\nxyz", "abc\r\nxyz"].iter() {
+            let ids = BoxIds::parse(input.as_bytes(), Segmentation::Scalars).unwrap();
+            assert_eq!(texts(&ids), vec!["abc", "xyz"], "{:?}", input);
+        }
+        assert_eq!(
+            BoxIds::parse(b"", Segmentation::Scalars)
+                .unwrap()
+                .ids()
+                .len(),
+            0
+        );
+    }
+
+    #[test]
+    fn units_depend_on_segmentation() {
+        let input = "ne\u{301}e\nnaaa\n".as_bytes();
+        assert!(BoxIds::parse(input, Segmentation::Graphemes).is_err());
+        let ids = BoxIds::parse(input, Segmentation::Scalars).unwrap();
+        assert_eq!(ids.width(), 4);
+
+        let ids = BoxIds::parse("நிலா\nநிலை\n".as_bytes(), Segmentation::Graphemes).unwrap();
+        assert_eq!(ids.width(), 2);
+        assert_eq!(ids.encode("நிx"), vec![ids.ids()[0][0], b'x' as u32]);
+        assert_eq!(ids.encode("லு")[0], u32::MAX);
+    }
+
+    #[test]
+    fn errors() {
+        assert!(BoxIds::parse(b"abc\nab\n", Segmentation::Scalars).is_err());
+        assert!(BoxIds::parse(b"a c\nabc\n", Segmentation::Scalars).is_err());
+        assert!(BoxIds::parse(b"a\tc\nabc", Segmentation::Scalars).is_err());
+        assert!(BoxIds::parse(b"ab\xff\nabc\n", Segmentation::Scalars).is_err());
+    }
+}
@@ -1,51 +1,283 @@
-fn part1(input: &[u8]) -> i64 {
-    let mut num_twos = 0;
-    let mut num_threes = 0;
-    for line in input.chunks(27) {
-        let mut num_seen = [0u8; 26];
-        assert!(line[26] == '\n' as u8);
-        for &v in &line[0..26] {
-            assert!(v >= 'a' as u8);
-            assert!(v <= 'z' as u8);
-            num_seen[(v - 'a' as u8) as usize] += 1;
-        }
-        if num_seen.iter().any(|&x| x == 2) {
-            num_twos += 1;
-        }
-        if num_seen.iter().any(|&x| x == 3) {
-            num_threes += 1;
+#![allow(clippy::needless_return)]
+
+use checksum::{Formula, LetterHistogram};
+use hamming::HammingIndex;
+use ids::BoxIds;
+use parselib::GenericError;
+use std::time::Instant;
+use unicode::Segmentation;
+
+mod checksum;
+mod hamming;
+mod ids;
+mod unicode;
+
+fn part1(ids: &BoxIds) -> usize {
+    return LetterHistogram::from_ids(&ids.ids(), &ids.alphabet).checksum(&Formula::default());
+}
+
+// Letters in common between two IDs
+fn common_letters(ids: &BoxIds, a: &[u32], b: &[u32]) -> String {
+    let common: Vec<u32> = a
+        .iter()
+        .zip(b.iter())
+        .filter(|(x, y)| x == y)
+        .map(|(&x, _)| x)
+        .collect();
+    return ids.alphabet.text(&common);
+}
+
+// Letters shared by the first two IDs that differ in exactly one position
+fn part2(ids: &BoxIds) -> Option<String> {
+    let list = ids.ids();
+    return hamming::find_one_apart(&list).map(|(a, b, _)| common_letters(ids, list[a], list[b]));
+}
+
+// Compares every pair of IDs
+fn part2_naive(ids: &BoxIds) -> Option<String> {
+    let list = ids.ids();
+    let mut iter_line1 = list.iter();
+    while let Some(line1) = iter_line1.next() {
+        for line2 in iter_line1.clone() {
+            if hamming::hamming_distance(line1, line2) == 1 {
+                return Some(common_letters(ids, line1, line2));
+            }
         }
     }
-    return num_twos * num_threes;
+    return None;
 }
 
-fn part2(input: &[u8]) -> String {
-    let mut iter_line1 = input.chunks(27);
-    let mut result = String::with_capacity(26);
-    while let Some(line1) = iter_line1.next() {
-        let mut iter_line2 = iter_line1.clone();
-        while let Some(line2) = iter_line2.next() {
-            result.clear();
-            for (&letter1, &letter2) in line1[0..26].iter().zip(line2[0..26].iter()) {
-                if letter1 == letter2 {
-                    result.push(letter1 as char);
-                }
+// Random lowercase IDs, with a pair one apart near the end so the naive search has to look at
+// almost every pair
+fn random_ids(count: usize, width: usize, seed: u64) -> Vec<Vec<u8>> {
+    let mut random = parselib::Random::new(seed);
+    let mut next_random = || b'a' + random.below(26) as u8;
+    let mut ids: Vec<Vec<u8>> = (0..count)
+        .map(|_| (0..width).map(|_| next_random()).collect())
+        .collect();
+    let mut near = ids[count - 2].clone();
+    near[width / 2] = if near[width / 2] == b'z' {
+        b'a'
+    } else {
+        near[width / 2] + 1
+    };
+    ids[count - 1] = near;
+    return ids;
+}
+
+fn bench() {
+    for &(count, width) in &[(250, 26), (2000, 26), (10_000, 26), (10_000, 100)] {
+        let mut input = Vec::new();
+        for id in random_ids(count, width, count as u64 + width as u64) {
+            input.extend_from_slice(&id);
+            input.push(b'\n');
+        }
+        let ids = BoxIds::parse(&input, Segmentation::Scalars).unwrap();
+
+        let start = Instant::now();
+        let bucketed = part2(&ids);
+        let bucketed_time = start.elapsed();
+
+        let start = Instant::now();
+        let naive = part2_naive(&ids);
+        let naive_time = start.elapsed();
+
+        assert!(bucketed == naive);
+        println!(
+            "{:>6} IDs of {:>3} letters: {}",
+            count,
+            width,
+            parselib::compare_times("bucketed", bucketed_time, "naive", naive_time)
+        );
+    }
+}
+
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let mut segmentation = Segmentation::Scalars;
+    let mut formula = Formula::default();
+    let mut positional: Vec<String> = Vec::new();
+    let mut iter = std::env::args().skip(1);
+    while let Some(arg) = iter.next() {
+        match arg.as_str() {
+            "--units" => {
+                segmentation = iter
+                    .next()
+                    .ok_or_else(|| {
+                        GenericError::new("expected scalars or graphemes after --units")
+                    })?
+                    .parse()?;
             }
-            if result.len() == 25 {
-                return result;
+            "--formula" => {
+                formula = iter
+                    .next()
+                    .ok_or_else(|| GenericError::new("expected a formula after --formula"))?
+                    .parse()?;
             }
+            _ => positional.push(arg),
+        }
+    }
+    let command = positional.first().map(String::as_str);
+    match command {
+        Some("bench") => {
+            bench();
+            return Ok(());
+        }
+        Some("report") | Some("near") | None => {}
+        Some(command) => {
+            return Err(Box::new(GenericError::new(&format!(
+                "unknown command {}",
+                command
+            ))))
         }
     }
 
-    panic!("No match found");
+    let input = parselib::load_bytes("input.txt")?;
+    let ids = BoxIds::parse(&input, segmentation)?;
+    match command {
+        Some("report") => {
+            let histogram = LetterHistogram::from_ids(&ids.ids(), &ids.alphabet);
+            print!("{}", histogram);
+            println!("checksum: {}", histogram.checksum(&formula));
+        }
+        Some("near") => {
+            let query = positional
+                .get(1)
+                .ok_or_else(|| GenericError::new("expected an ID to search near"))?;
+            let distance = match positional.get(2) {
+                Some(distance) => distance.parse()?,
+                None => 1,
+            };
+            let list = ids.ids();
+            let index = HammingIndex::new(&list, distance);
+            let encoded = ids.encode(query);
+            let matches = index.within(&encoded, distance).ok_or_else(|| {
+                GenericError::new(&format!(
+                    "{} has {} characters but the IDs have {}",
+                    query,
+                    encoded.len(),
+                    ids.width()
+                ))
+            })?;
+            for (i, d) in matches {
+                println!(
+                    "{} (line {}, distance {})",
+                    ids.alphabet.text(list[i]),
+                    i + 1,
+                    d
+                );
+            }
+        }
+        _ => {
+            println!("part1: {}", part1(&ids));
+            match part2(&ids) {
+                Some(common) => println!("part2: {}", common),
+                None => println!("part2: no IDs differ by exactly one character"),
+            }
+        }
+    }
+    Ok(())
 }
 
-fn main() {
-    // Assume input is unix ascii/utf8 formatted text file with no byte-order-mark.  That way we
-    // can interpret the file as bytes where each line is 27 bytes long with the first 26 bytes
-    // are ascii characters between 'a' and 'z', and the 27th byte is a unix newline
-    let input = parselib::load_bytes("input.txt").expect("Couldn't open input");
-    assert!(input.len() % 27 == 0);
-    println!("part1: {}", part1(input.as_slice()));
-    println!("part2: {}", part2(input.as_slice()));
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn puzzle_examples() {
+        let ids = BoxIds::parse(
+            b"abcdef\nbababc\nabbcde\nabcccd\naabcdd\nabcdee\nababab\n",
+            Segmentation::Scalars,
+        )
+        .unwrap();
+        assert_eq!(part1(&ids), 12);
+        let ids = BoxIds::parse(
+            b"abcde\nfghij\nklmno\npqrst\nfguij\naxcye\nwvxyz\n",
+            Segmentation::Scalars,
+        )
+        .unwrap();
+        assert_eq!(part2(&ids).as_deref(), Some("fgij"));
+        assert_eq!(part2_naive(&ids).as_deref(), Some("fgij"));
+    }
+
+    // IDs with pairs of letters and the letters in common between the two IDs that differ by one,
+    // depending on what counts as a letter
+    #[test]
+    fn unicode_ids() {
+        use Segmentation::{Graphemes, Scalars};
+        let examples: &[(&str, Segmentation, &str, usize, Option<&str>)] = &[
+            (
+                "accented scalars",
+                Scalars,
+                "añob\nañoc\nxyzw\n",
+                0,
+                Some("año"),
+            ),
+            ("precomposed repeat", Scalars, "ééab\nxyzw\n", 1, None),
+            (
+                "combining accent as scalars",
+                Scalars,
+                "ne\u{301}e\nne\u{300}e\n",
+                2,
+                Some("nee"),
+            ),
+            (
+                "combining accent as graphemes",
+                Graphemes,
+                "ne\u{301}e\nne\u{300}e\n",
+                0,
+                Some("ne"),
+            ),
+            (
+                "combining repeat as graphemes",
+                Graphemes,
+                "e\u{301}e\u{301}ab\nwxyz\n",
+                1,
+                None,
+            ),
+            ("flags as scalars", Scalars, "🇫🇷🇩🇪\n🇫🇷🇮🇹\n", 0, None),
+            (
+                "flags as graphemes",
+                Graphemes,
+                "🇫🇷🇩🇪\n🇫🇷🇮🇹\n",
+                0,
+                Some("🇫🇷"),
+            ),
+            (
+                "emoji sequence as graphemes",
+                Graphemes,
+                "👩\u{200D}👩\u{200D}👧x\n👩\u{200D}👩\u{200D}👦x\n",
+                0,
+                Some("x"),
+            ),
+            (
+                "emoji sequence as scalars",
+                Scalars,
+                "👩\u{200D}👩\u{200D}👧x\n👩\u{200D}👩\u{200D}👦x\n",
+                2,
+                Some("👩\u{200D}👩\u{200D}x"),
+            ),
+            (
+                "hangul jamo as graphemes",
+                Graphemes,
+                "\u{1100}\u{1161}\u{11A8}가\n\u{1100}\u{1161}\u{11A8}나\n",
+                0,
+                Some("\u{1100}\u{1161}\u{11A8}"),
+            ),
+            ("tamil as scalars", Scalars, "நிலா\nநிலை\n", 0, Some("நில")),
+            (
+                "tamil as graphemes",
+                Graphemes,
+                "நிலா\nநிலை\n",
+                0,
+                Some("நி"),
+            ),
+        ];
+        for &(name, segmentation, input, twos, common) in examples {
+            let ids = BoxIds::parse(input.as_bytes(), segmentation).unwrap();
+            let histogram = LetterHistogram::from_ids(&ids.ids(), &ids.alphabet);
+            assert_eq!(histogram.ids_with_count(2), twos, "{}", name);
+            assert_eq!(part2(&ids).as_deref(), common, "{}", name);
+            assert_eq!(part2_naive(&ids).as_deref(), common, "{}", name);
+        }
+    }
 }
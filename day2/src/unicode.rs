@@ -0,0 +1,98 @@
+use parselib::GenericError;
+use std::str::FromStr;
+use unicode_segmentation::UnicodeSegmentation;
+
+// Splitting IDs into the units that get compared and counted, either Unicode scalar values or
+// extended grapheme clusters, so that something like an accent written as a separate combining
+// character, a flag or an emoji family can count as one unit.
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Segmentation {
+    Scalars,
+    Graphemes,
+}
+
+impl Segmentation {
+    pub fn split(self, s: &str) -> Vec<&str> {
+        return match self {
+            Segmentation::Scalars => s
+                .char_indices()
+                .map(|(i, c)| &s[i..i + c.len_utf8()])
+                .collect(),
+            Segmentation::Graphemes => s.graphemes(true).collect(),
+        };
+    }
+}
+
+impl FromStr for Segmentation {
+    type Err = GenericError;
+
+    fn from_str(s: &str) -> Result<Segmentation, GenericError> {
+        return match s {
+            "scalars" => Ok(Segmentation::Scalars),
+            "graphemes" => Ok(Segmentation::Graphemes),
+            _ => Err(GenericError::new(&format!(
+                "unknown units {:?} (expected scalars or graphemes)",
+                s
+            ))),
+        };
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn scalars() {
+        assert_eq!(Segmentation::Scalars.split("añ"), vec!["a", "ñ"]);
+        assert_eq!(
+            Segmentation::Scalars.split("ne\u{301}"),
+            vec!["n", "e", "\u{301}"]
+        );
+        assert!(Segmentation::Scalars.split("").is_empty());
+    }
+
+    #[test]
+    fn combining_marks() {
+        assert_eq!(
+            Segmentation::Graphemes.split("ne\u{301}e"),
+            vec!["n", "e\u{301}", "e"]
+        );
+        // Tamil vowel signs are spacing marks that join the consonant before them
+        assert_eq!(Segmentation::Graphemes.split("நிலா"), vec!["நி", "லா"]);
+        // Consonants joined by a virama form a single conjunct
+        assert_eq!(Segmentation::Graphemes.split("नमस्ते"), vec!["न", "म", "स्ते"]);
+    }
+
+    #[test]
+    fn flags_and_emoji() {
+        assert_eq!(Segmentation::Graphemes.split("🇫🇷🇩🇪"), vec!["🇫🇷", "🇩🇪"]);
+        assert_eq!(
+            Segmentation::Graphemes.split("👩\u{200D}👩\u{200D}👧x"),
+            vec!["👩\u{200D}👩\u{200D}👧", "x"]
+        );
+        assert_eq!(Segmentation::Graphemes.split("👍🏽!"), vec!["👍🏽", "!"]);
+    }
+
+    #[test]
+    fn hangul() {
+        assert_eq!(
+            Segmentation::Graphemes.split("\u{1100}\u{1161}\u{11A8}가"),
+            vec!["\u{1100}\u{1161}\u{11A8}", "가"]
+        );
+    }
+
+    #[test]
+    fn parse_units() {
+        assert_eq!(
+            "scalars".parse::<Segmentation>().unwrap(),
+            Segmentation::Scalars
+        );
+        assert_eq!(
+            "graphemes".parse::<Segmentation>().unwrap(),
+            Segmentation::Graphemes
+        );
+        assert!("bytes".parse::<Segmentation>().is_err());
+    }
+}
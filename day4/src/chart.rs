@@ -0,0 +1,282 @@
+use super::stats::SleepStats;
+use super::{Date, Shift, MINUTES_PER_DAY};
+use std::ops::Range;
+
+const HEAT_LEVELS: &[u8] = b" .:-=+*#%@";
+
+#[derive(Debug, Clone, Default)]
+pub struct ChartFilter {
+    pub guard: Option<usize>,
+    pub from: Option<Date>,
+    pub to: Option<Date>,
+}
+
+impl ChartFilter {
+    fn matches(&self, shift: &Shift) -> bool {
+        return self.guard.is_none_or(|guard| shift.guard == guard)
+            && self.from.is_none_or(|from| shift.date >= from)
+            && self.to.is_none_or(|to| shift.date <= to);
+    }
+}
+
+// Naps relative to midnight of the shift's date, so a nap starting at 23:50 the previous evening
+// begins at minute -10
+fn relative_naps(shift: &Shift) -> impl Iterator<Item = Range<i64>> + '_ {
+    let midnight = shift.date.day_number() * MINUTES_PER_DAY as i64;
+    return shift
+        .naps
+        .iter()
+        .map(move |nap| nap.start - midnight..nap.end - midnight);
+}
+
+// Whole hours covering every nap, defaulting to the puzzle's midnight hour
+fn chart_window<'a, I: Iterator<Item = &'a Shift>>(shifts: I) -> Range<i64> {
+    let mut start = 0;
+    let mut end = 60;
+    for shift in shifts {
+        for nap in relative_naps(shift) {
+            start = start.min(nap.start.div_euclid(60) * 60);
+            end = end.max((nap.end + 59).div_euclid(60) * 60);
+        }
+    }
+    return start..end;
+}
+
+fn minute_header(window: &Range<i64>, indent: usize) -> String {
+    let mut result = String::new();
+    if window.end - window.start > 60 {
+        let mut hours = vec![b' '; (window.end - window.start) as usize];
+        for minute in window.clone().step_by(60) {
+            let label = format!("{:02}", minute.div_euclid(60).rem_euclid(24));
+            let offset = (minute - window.start) as usize;
+            hours[offset..offset + 2].copy_from_slice(label.as_bytes());
+        }
+        result.push_str(&" ".repeat(indent));
+        result.push_str(String::from_utf8(hours).unwrap().trim_end());
+        result.push('\n');
+    }
+    for digit in &[10, 1] {
+        result.push_str(&" ".repeat(indent));
+        for minute in window.clone() {
+            result.push_str(&(minute.rem_euclid(60) / digit % 10).to_string());
+        }
+        result.push('\n');
+    }
+    return result;
+}
+
+pub fn render_sleep_chart(shifts: &[Shift], filter: &ChartFilter) -> String {
+    let selected: Vec<&Shift> = shifts.iter().filter(|s| filter.matches(s)).collect();
+    let window = chart_window(selected.iter().cloned());
+    let id_width = selected
+        .iter()
+        .map(|s| s.guard.to_string().len() + 1)
+        .max()
+        .unwrap_or(2)
+        .max(2);
+
+    let mut result = format!("{:<12}{:<w$}  Minute\n", "Date", "ID", w = id_width);
+    result.push_str(&minute_header(&window, 12 + id_width + 2));
+
+    for shift in selected {
+        let mut row = vec![b'.'; (window.end - window.start) as usize];
+        for nap in relative_naps(shift) {
+            for minute in nap {
+                row[(minute - window.start) as usize] = b'#';
+            }
+        }
+        result.push_str(&format!(
+            "{:<12}{:<w$}  {}\n",
+            shift.date.to_string(),
+            format!("#{}", shift.guard),
+            String::from_utf8(row).unwrap(),
+            w = id_width
+        ));
+    }
+    return result;
+}
+
+pub fn render_heatmap(shifts: &[Shift], filter: &ChartFilter) -> String {
+    let selected: Vec<&Shift> = shifts.iter().filter(|s| filter.matches(s)).collect();
+    let stats = SleepStats::from_shifts(selected.iter().cloned());
+
+    let window = chart_window(selected.iter().cloned());
+    let id_width = stats
+        .guards()
+        .map(|guard| guard.to_string().len() + 1)
+        .max()
+        .unwrap_or(2)
+        .max(2);
+
+    let mut result = format!("{:<w$}  Minute\n", "ID", w = id_width);
+    result.push_str(&minute_header(&window, id_width + 2));
+    for guard in stats.guards() {
+        let counts = stats.minute_histogram(guard).unwrap();
+        let max_count = *counts.iter().max().unwrap();
+        let row: String = window
+            .clone()
+            .map(|minute| {
+                let count = counts[minute.rem_euclid(MINUTES_PER_DAY as i64) as usize];
+                let level = if count == 0 {
+                    0
+                } else {
+                    (count * (HEAT_LEVELS.len() - 1)).div_ceil(max_count)
+                };
+                HEAT_LEVELS[level] as char
+            })
+            .collect();
+        result.push_str(&format!(
+            "{:<w$}  {}  ({} minutes, max {})\n",
+            format!("#{}", guard),
+            row,
+            stats.total_minutes(guard),
+            max_count,
+            w = id_width
+        ));
+    }
+    return result;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const EXAMPLE: &str = "\
+[1518-11-01 00:00] Guard #10 begins shift
+[1518-11-01 00:05] falls asleep
+[1518-11-01 00:25] wakes up
+[1518-11-01 00:30] falls asleep
+[1518-11-01 00:55] wakes up
+[1518-11-01 23:58] Guard #99 begins shift
+[1518-11-02 00:40] falls asleep
+[1518-11-02 00:50] wakes up
+[1518-11-03 00:05] Guard #10 begins shift
+[1518-11-03 00:24] falls asleep
+[1518-11-03 00:29] wakes up
+[1518-11-04 00:02] Guard #99 begins shift
+[1518-11-04 00:36] falls asleep
+[1518-11-04 00:46] wakes up
+[1518-11-05 00:03] Guard #99 begins shift
+[1518-11-05 00:45] falls asleep
+[1518-11-05 00:55] wakes up
+";
+
+    // A nap from 23:50 the evening before, which widens the chart to two hours
+    const LATE_NAP: &str = "\
+[1518-11-05 23:40] Guard #99 begins shift
+[1518-11-05 23:50] falls asleep
+[1518-11-06 00:05] wakes up
+";
+
+    fn shifts(log: &str) -> Vec<Shift> {
+        return crate::reconstruct_shifts(&crate::parse_log(log).unwrap()).unwrap();
+    }
+
+    fn lines(lines: &[&str]) -> String {
+        return lines.iter().map(|line| format!("{}\n", line)).collect();
+    }
+
+    #[test]
+    fn puzzle_example_chart() {
+        let shifts = shifts(EXAMPLE);
+        assert_eq!(
+            render_sleep_chart(&shifts, &ChartFilter::default()),
+            lines(&[
+                "Date        ID   Minute",
+                "                 000000000011111111112222222222333333333344444444445555555555",
+                "                 012345678901234567890123456789012345678901234567890123456789",
+                "1518-11-01  #10  .....####################.....#########################.....",
+                "1518-11-02  #99  ........................................##########..........",
+                "1518-11-03  #10  ........................#####...............................",
+                "1518-11-04  #99  ....................................##########..............",
+                "1518-11-05  #99  .............................................##########.....",
+            ])
+        );
+        assert_eq!(
+            render_heatmap(&shifts, &ChartFilter::default()),
+            lines(&[
+                "ID   Minute",
+                "     000000000011111111112222222222333333333344444444445555555555",
+                "     012345678901234567890123456789012345678901234567890123456789",
+                "#10       +++++++++++++++++++@++++ +++++++++++++++++++++++++       (50 minutes, max 2)",
+                "#99                                      ----*****@****-----       (30 minutes, max 3)",
+            ])
+        );
+    }
+
+    #[test]
+    fn filtered_chart_across_midnight() {
+        let shifts = shifts(&format!("{}{}", EXAMPLE, LATE_NAP));
+        let filter = ChartFilter {
+            guard: Some(99),
+            from: Some("1518-11-03".parse().unwrap()),
+            to: Some("1518-11-06".parse().unwrap()),
+        };
+        let ruler = [
+            "000000000011111111112222222222333333333344444444445555555555",
+            "000000000011111111112222222222333333333344444444445555555555",
+        ]
+        .concat();
+        let digits = [
+            "012345678901234567890123456789012345678901234567890123456789",
+            "012345678901234567890123456789012345678901234567890123456789",
+        ]
+        .concat();
+        assert_eq!(
+            render_sleep_chart(&shifts, &filter),
+            lines(&[
+                "Date        ID   Minute",
+                &format!("{:17}23{:58}00", "", ""),
+                &format!("{:17}{}", "", ruler),
+                &format!("{:17}{}", "", digits),
+                &format!(
+                    "1518-11-04  #99  {}{}",
+                    ".".repeat(60),
+                    "....................................##########.............."
+                ),
+                &format!(
+                    "1518-11-05  #99  {}{}",
+                    ".".repeat(60),
+                    ".............................................##########....."
+                ),
+                &format!(
+                    "1518-11-06  #99  {}{}{}",
+                    ".".repeat(50),
+                    "#".repeat(15),
+                    ".".repeat(55)
+                ),
+            ])
+        );
+        assert_eq!(
+            render_heatmap(&shifts, &filter),
+            lines(&[
+                "ID   Minute",
+                &format!("{:5}23{:58}00", "", ""),
+                &format!("{:5}{}", "", ruler),
+                &format!("{:5}{}", "", digits),
+                &format!(
+                    "#99  {}{}{}{}@{}{}  (35 minutes, max 2)",
+                    " ".repeat(50),
+                    "+".repeat(15),
+                    " ".repeat(31),
+                    "+".repeat(9),
+                    "+".repeat(9),
+                    " ".repeat(5),
+                ),
+            ])
+        );
+
+        let early = ChartFilter {
+            to: Some("1518-11-02".parse().unwrap()),
+            ..ChartFilter::default()
+        };
+        let chart = render_sleep_chart(&shifts, &early);
+        assert_eq!(chart.lines().count(), 5);
+        assert!(chart
+            .lines()
+            .nth(1)
+            .unwrap()
+            .trim_start()
+            .starts_with("0000"));
+    }
+}
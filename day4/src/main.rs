@@ -1,14 +1,140 @@
+#![allow(clippy::needless_return)]
+
+use parselib::GenericError;
+use stats::{SleepStats, Strategy};
+mod chart;
+mod stats;
+
 use regex::Regex;
-use std::collections::HashMap;
+use std::fmt;
+use std::ops::Range;
+use std::str::FromStr;
+
+const MINUTES_PER_DAY: usize = 24 * 60;
+
+// Guards arriving in the last hour before midnight are starting the next day's shift early, as in
+// the puzzle, where shifts begin just before or just after midnight.  Shifts that begin any
+// earlier are dated by the day they begin.
+const EARLY_START_MINUTE: usize = 23 * 60;
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Ord, PartialOrd, Hash)]
+struct Date {
+    year: i32,
+    month: u8,
+    day: u8,
+}
+
+fn is_leap_year(year: i32) -> bool {
+    return year % 4 == 0 && (year % 100 != 0 || year % 400 == 0);
+}
+
+fn days_in_month(year: i32, month: u8) -> u8 {
+    return match month {
+        2 if is_leap_year(year) => 29,
+        2 => 28,
+        4 | 6 | 9 | 11 => 30,
+        _ => 31,
+    };
+}
+
+impl Date {
+    fn is_valid(&self) -> bool {
+        return (1..=12).contains(&self.month)
+            && (1..=days_in_month(self.year, self.month)).contains(&self.day);
+    }
+
+    // Days since 0000-03-01 in the proleptic Gregorian calendar
+    fn day_number(&self) -> i64 {
+        let year = if self.month <= 2 {
+            self.year - 1
+        } else {
+            self.year
+        } as i64;
+        let era = if year >= 0 { year } else { year - 399 } / 400;
+        let year_of_era = year - era * 400;
+        let month = self.month as i64;
+        let day_of_year =
+            (153 * (if month > 2 { month - 3 } else { month + 9 }) + 2) / 5 + self.day as i64 - 1;
+        let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+        return era * 146097 + day_of_era;
+    }
+
+    fn from_day_number(day_number: i64) -> Date {
+        let era = if day_number >= 0 {
+            day_number
+        } else {
+            day_number - 146096
+        } / 146097;
+        let day_of_era = day_number - era * 146097;
+        let year_of_era =
+            (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146096) / 365;
+        let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+        let shifted_month = (5 * day_of_year + 2) / 153;
+        let day = day_of_year - (153 * shifted_month + 2) / 5 + 1;
+        let month = if shifted_month < 10 {
+            shifted_month + 3
+        } else {
+            shifted_month - 9
+        };
+        let year = year_of_era + era * 400 + if month <= 2 { 1 } else { 0 };
+        return Date {
+            year: year as i32,
+            month: month as u8,
+            day: day as u8,
+        };
+    }
+}
+
+impl fmt::Display for Date {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{:04}-{:02}-{:02}", self.year, self.month, self.day)
+    }
+}
+
+impl FromStr for Date {
+    type Err = parselib::ParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut iter = s.trim().rsplitn(3, '-');
+        let mut next_field = || iter.next().ok_or(parselib::ParseError);
+        let day: u8 = next_field()?.parse().map_err(|_| parselib::ParseError)?;
+        let month: u8 = next_field()?.parse().map_err(|_| parselib::ParseError)?;
+        let year = next_field()?.parse().map_err(|_| parselib::ParseError)?;
+        let date = Date { year, month, day };
+        if !date.is_valid() {
+            return Err(parselib::ParseError);
+        }
+        return Ok(date);
+    }
+}
 
 #[derive(Debug, Copy, Clone, PartialEq, Eq, Ord, PartialOrd)]
 struct Timestamp {
+    year: i32,
     month: u8,
     day: u8,
     hour: u8,
     minute: u8,
 }
 
+impl Timestamp {
+    fn date(&self) -> Date {
+        return Date {
+            year: self.year,
+            month: self.month,
+            day: self.day,
+        };
+    }
+
+    fn minute_of_day(&self) -> usize {
+        return self.hour as usize * 60 + self.minute as usize;
+    }
+
+    fn absolute_minute(&self) -> i64 {
+        return self.date().day_number() * MINUTES_PER_DAY as i64 + self.minute_of_day() as i64;
+    }
+}
+
 #[derive(Debug, Copy, Clone, PartialEq, Eq, Ord, PartialOrd)]
 enum Event {
     FallAsleep,
@@ -18,89 +144,408 @@ enum Event {
 
 fn parse_log_entry(s: &str) -> Result<(Timestamp, Event), parselib::ParseError> {
     lazy_static::lazy_static! {
-        static ref DATE_RE: Regex = Regex::new(r"\[\d+-(\d\d)-(\d\d) (\d\d):(\d\d)\]").unwrap();
+        static ref DATE_RE: Regex =
+            Regex::new(r"\[(-?\d+)-(\d\d)-(\d\d) (\d\d):(\d\d)\]").unwrap();
         static ref BEGIN_SHIFT_RE: Regex = Regex::new(r"Guard #(\d+)").unwrap();
     };
 
     let date_captures = DATE_RE.captures(s).ok_or(parselib::ParseError)?;
+    let field = |i| date_captures.get(i).unwrap().as_str();
 
     let event = if let Some(capture) = BEGIN_SHIFT_RE.captures(s) {
-        Event::BeginShift(capture.get(1).unwrap().as_str().parse().unwrap())
-    } else if s.find("falls").is_some() {
+        Event::BeginShift(
+            capture
+                .get(1)
+                .unwrap()
+                .as_str()
+                .parse()
+                .map_err(|_| parselib::ParseError)?,
+        )
+    } else if s.contains("falls") {
         Event::FallAsleep
     } else {
         Event::WakeUp
     };
 
-    return Ok((
-        Timestamp {
-            month: date_captures.get(1).unwrap().as_str().parse().unwrap(),
-            day: date_captures.get(2).unwrap().as_str().parse().unwrap(),
-            hour: date_captures.get(3).unwrap().as_str().parse().unwrap(),
-            minute: date_captures.get(4).unwrap().as_str().parse().unwrap(),
-        },
-        event,
-    ));
-}
-
-fn part12(sorted_entries: &[(Timestamp, Event)]) -> (usize, usize) {
-    let mut guard_minutes_asleep = HashMap::new();
-    let mut current_guard = 0usize;
-    let mut last_event_minute = 0;
-    let mut most_minutes = 0;
-    let mut most_minutes_guard = 0;
-    for (time, event) in sorted_entries {
-        match event {
-            &Event::BeginShift(guard_id) => {
-                current_guard = guard_id;
+    let timestamp = Timestamp {
+        year: field(1).parse().map_err(|_| parselib::ParseError)?,
+        month: field(2).parse().unwrap(),
+        day: field(3).parse().unwrap(),
+        hour: field(4).parse().unwrap(),
+        minute: field(5).parse().unwrap(),
+    };
+    if !timestamp.date().is_valid() || timestamp.hour > 23 || timestamp.minute > 59 {
+        return Err(parselib::ParseError);
+    }
+
+    return Ok((timestamp, event));
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Ord, PartialOrd)]
+struct LogEntry {
+    time: Timestamp,
+    event: Event,
+    line: usize,
+}
+
+fn parse_log(text: &str) -> Result<Vec<LogEntry>, GenericError> {
+    let mut entries = Vec::new();
+    for (i, line) in text.lines().enumerate() {
+        if line.trim().is_empty() {
+            continue;
+        }
+        let (time, event) = parse_log_entry(line)
+            .map_err(|_| GenericError::new(&format!("line {}: malformed log entry", i + 1)))?;
+        entries.push(LogEntry {
+            time,
+            event,
+            line: i + 1,
+        });
+    }
+    entries.sort();
+    return Ok(entries);
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Shift {
+    guard: usize,
+    date: Date,
+    // Absolute minutes (see Timestamp::absolute_minute) the guard was asleep
+    naps: Vec<Range<i64>>,
+}
+
+fn reconstruct_shifts(sorted_entries: &[LogEntry]) -> Result<Vec<Shift>, GenericError> {
+    let error = |entry: &LogEntry, message: &str| {
+        GenericError::new(&format!("line {}: {}", entry.line, message))
+    };
+
+    let mut shifts: Vec<Shift> = Vec::new();
+    let mut asleep_since: Option<&LogEntry> = None;
+    for entry in sorted_entries {
+        match entry.event {
+            Event::BeginShift(guard) => {
+                if let Some(sleep_entry) = asleep_since {
+                    return Err(error(sleep_entry, "guard falls asleep but never wakes up"));
+                }
+                let mut date = entry.time.date();
+                if entry.time.minute_of_day() >= EARLY_START_MINUTE {
+                    date = Date::from_day_number(date.day_number() + 1);
+                }
+                shifts.push(Shift {
+                    guard,
+                    date,
+                    naps: Vec::new(),
+                });
             }
-            Event::WakeUp => {
-                let (ref mut total_minutes, ref mut per_minute) = guard_minutes_asleep
-                    .entry(current_guard)
-                    .or_insert((0usize, [0usize; 60]));
-                for minute in last_event_minute..time.minute {
-                    per_minute[minute as usize] += 1;
+            Event::FallAsleep => {
+                if shifts.is_empty() {
+                    return Err(error(entry, "guard falls asleep before any shift begins"));
                 }
-                *total_minutes += (time.minute - last_event_minute) as usize;
-                if *total_minutes > most_minutes {
-                    most_minutes = *total_minutes;
-                    most_minutes_guard = current_guard;
+                if asleep_since.is_some() {
+                    return Err(error(entry, "guard falls asleep while already asleep"));
                 }
+                asleep_since = Some(entry);
             }
-            Event::FallAsleep => {}
-        };
-        last_event_minute = if time.hour != 0 { 0 } else { time.minute };
+            Event::WakeUp => {
+                let shift = shifts
+                    .last_mut()
+                    .ok_or_else(|| error(entry, "guard wakes up before any shift begins"))?;
+                let start = asleep_since
+                    .take()
+                    .ok_or_else(|| error(entry, "guard wakes up without falling asleep"))?;
+                shift
+                    .naps
+                    .push(start.time.absolute_minute()..entry.time.absolute_minute());
+            }
+        }
+    }
+    if let Some(sleep_entry) = asleep_since {
+        return Err(error(sleep_entry, "guard falls asleep but never wakes up"));
     }
 
-    let (_, minutes) = guard_minutes_asleep[&most_minutes_guard];
-    let (guard_most_asleep_minute, _) = minutes.iter().enumerate().max_by_key(|&(_, v)| v).unwrap();
+    return Ok(shifts);
+}
 
-    // Part 2
-    let mut most_asleep_minute = 0;
-    let mut most_asleep_count = 0;
-    let mut most_asleep_minute_guard = 0;
+fn strategy_answer(strategy: &dyn Strategy, stats: &SleepStats) -> Result<usize, GenericError> {
+    let (guard, minute) = strategy.select(stats).ok_or_else(|| {
+        GenericError::new(&format!(
+            "strategy {} found no sleeping guard",
+            strategy.name()
+        ))
+    })?;
+    return Ok(guard * minute);
+}
 
-    for (guard, (_, minutes)) in guard_minutes_asleep {
-        for (i, &asleep_count) in minutes.iter().enumerate() {
-            if asleep_count > most_asleep_count {
-                most_asleep_count = asleep_count;
-                most_asleep_minute = i;
-                most_asleep_minute_guard = guard;
-            }
+fn parse_chart_filter(args: &[String]) -> Result<chart::ChartFilter, GenericError> {
+    let mut filter = chart::ChartFilter::default();
+    let mut iter = args.iter();
+    while let Some(arg) = iter.next() {
+        let value = iter
+            .next()
+            .ok_or_else(|| GenericError::new(&format!("missing value for {}", arg)))?;
+        let invalid = || GenericError::new(&format!("invalid value for {}: {}", arg, value));
+        match arg.as_str() {
+            "--guard" => filter.guard = Some(value.parse().map_err(|_| invalid())?),
+            "--from" => filter.from = Some(value.parse().map_err(|_| invalid())?),
+            "--to" => filter.to = Some(value.parse().map_err(|_| invalid())?),
+            _ => return Err(GenericError::new(&format!("unknown option {}", arg))),
         }
     }
+    return Ok(filter);
+}
 
-    return (
-        guard_most_asleep_minute * most_minutes_guard,
-        most_asleep_minute * most_asleep_minute_guard,
-    );
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let input = parse_log(&parselib::load_text_file("input.txt")?)?;
+    let shifts = reconstruct_shifts(input.as_slice())?;
+    match args.first().map(String::as_str) {
+        None => {
+            let stats = SleepStats::from_shifts(&shifts);
+            println!(
+                "part1: {}",
+                strategy_answer(&stats::MostMinutesAsleep, &stats)?
+            );
+            println!(
+                "part2: {}",
+                strategy_answer(&stats::MostFrequentMinute, &stats)?
+            );
+        }
+        Some("strategy") => {
+            let stats = SleepStats::from_shifts(&shifts);
+            let selected = match args.get(1) {
+                Some(name) => vec![stats::find_strategy(name)
+                    .ok_or_else(|| GenericError::new(&format!("unknown strategy {}", name)))?],
+                None => stats::strategies(),
+            };
+            for strategy in selected {
+                println!(
+                    "{}: {} ({})",
+                    strategy.name(),
+                    strategy_answer(strategy.as_ref(), &stats)?,
+                    strategy.description()
+                );
+            }
+        }
+        Some("top") => {
+            let n = match args.get(1) {
+                Some(n) => n
+                    .parse()
+                    .map_err(|_| GenericError::new(&format!("invalid count {}", n)))?,
+                None => 5,
+            };
+            let stats = SleepStats::from_shifts(&shifts);
+            for (guard, minutes) in stats.top_sleepiest(n) {
+                let (minute, count) = stats.most_likely_minute(guard).unwrap_or((0, 0));
+                println!(
+                    "#{}: {} minutes asleep, most likely at {:02}:{:02} ({} times)",
+                    guard,
+                    minutes,
+                    minute / 60,
+                    minute % 60,
+                    count
+                );
+            }
+        }
+        Some("chart") => {
+            let filter = parse_chart_filter(&args[1..])?;
+            print!("{}", chart::render_sleep_chart(&shifts, &filter));
+        }
+        Some("heatmap") => {
+            let filter = parse_chart_filter(&args[1..])?;
+            print!("{}", chart::render_heatmap(&shifts, &filter));
+        }
+        Some(command) => {
+            return Err(Box::new(GenericError::new(&format!(
+                "unknown command {} (expected chart, heatmap, strategy or top)",
+                command
+            ))));
+        }
+    }
+    Ok(())
 }
 
-fn main() {
-    let mut input =
-        parselib::parse_lines_fn("input.txt", parse_log_entry).expect("Could not parse input");
-    input.sort();
-    let result = part12(input.as_slice());
-    println!("part1: {}", result.0);
-    println!("part2: {}", result.1);
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const EXAMPLE: &str = "\
+[1518-11-01 00:00] Guard #10 begins shift
+[1518-11-01 00:05] falls asleep
+[1518-11-01 00:25] wakes up
+[1518-11-01 00:30] falls asleep
+[1518-11-01 00:55] wakes up
+[1518-11-01 23:58] Guard #99 begins shift
+[1518-11-02 00:40] falls asleep
+[1518-11-02 00:50] wakes up
+[1518-11-03 00:05] Guard #10 begins shift
+[1518-11-03 00:24] falls asleep
+[1518-11-03 00:29] wakes up
+[1518-11-04 00:02] Guard #99 begins shift
+[1518-11-04 00:36] falls asleep
+[1518-11-04 00:46] wakes up
+[1518-11-05 00:03] Guard #99 begins shift
+[1518-11-05 00:45] falls asleep
+[1518-11-05 00:55] wakes up
+";
+
+    fn shifts(log: &str) -> Result<Vec<Shift>, GenericError> {
+        return reconstruct_shifts(&parse_log(log)?);
+    }
+
+    fn error(log: &str) -> String {
+        return shifts(log).unwrap_err().to_string();
+    }
+
+    fn date(s: &str) -> Date {
+        return s.parse().unwrap();
+    }
+
+    #[test]
+    fn puzzle_example() {
+        let stats = SleepStats::from_shifts(&shifts(EXAMPLE).unwrap());
+        assert_eq!(
+            strategy_answer(&stats::MostMinutesAsleep, &stats).unwrap(),
+            240
+        );
+        assert_eq!(
+            strategy_answer(&stats::MostFrequentMinute, &stats).unwrap(),
+            4455
+        );
+    }
+
+    #[test]
+    fn day_numbers_round_trip() {
+        assert_eq!(date("0000-03-01").day_number(), 0);
+        let start = date("-0401-01-01").day_number();
+        let end = date("2401-12-31").day_number();
+        let mut previous = Date::from_day_number(start - 1);
+        for day_number in start..=end {
+            let date = Date::from_day_number(day_number);
+            assert!(date.is_valid(), "{}", date);
+            assert_eq!(date.day_number(), day_number, "{}", date);
+            assert!(date > previous);
+            previous = date;
+        }
+    }
+
+    #[test]
+    fn month_lengths() {
+        let next_day = |s: &str| Date::from_day_number(date(s).day_number() + 1).to_string();
+        assert_eq!(next_day("1518-02-28"), "1518-03-01");
+        assert_eq!(next_day("1516-02-28"), "1516-02-29");
+        assert_eq!(next_day("1500-02-28"), "1500-03-01");
+        assert_eq!(next_day("1600-02-28"), "1600-02-29");
+        assert_eq!(next_day("1518-04-30"), "1518-05-01");
+        assert_eq!(next_day("1518-12-31"), "1519-01-01");
+
+        for invalid in &[
+            "1518-02-31",
+            "1518-02-29",
+            "1500-02-29",
+            "1518-04-31",
+            "1518-13-01",
+        ] {
+            assert!(invalid.parse::<Date>().is_err(), "{}", invalid);
+        }
+        assert!("1600-02-29".parse::<Date>().is_ok());
+        assert_eq!(
+            parse_log(
+                "[1518-11-01 00:00] Guard #10 begins shift\n[1518-02-30 00:05] falls asleep\n"
+            )
+            .unwrap_err()
+            .to_string(),
+            "Error: line 2: malformed log entry"
+        );
+    }
+
+    #[test]
+    fn nap_across_midnight() {
+        let shifts = shifts(
+            "[1518-12-31 23:30] Guard #7 begins shift\n\
+             [1518-12-31 23:50] falls asleep\n\
+             [1519-01-01 00:10] wakes up\n",
+        )
+        .unwrap();
+        assert_eq!(shifts.len(), 1);
+        assert_eq!(shifts[0].date, date("1519-01-01"));
+        let nap = &shifts[0].naps[0];
+        assert_eq!(nap.end - nap.start, 20);
+
+        let stats = SleepStats::from_shifts(&shifts);
+        assert_eq!(stats.total_minutes(7), 20);
+        let histogram = stats.minute_histogram(7).unwrap();
+        assert_eq!(histogram[23 * 60 + 49], 0);
+        assert_eq!(histogram[23 * 60 + 50], 1);
+        assert_eq!(histogram[9], 1);
+        assert_eq!(histogram[10], 0);
+    }
+
+    #[test]
+    fn shifts_are_dated_by_start_unless_just_before_midnight() {
+        let shifts = shifts(
+            "[1518-03-01 14:00] Guard #1 begins shift\n\
+             [1518-03-01 22:59] Guard #2 begins shift\n\
+             [1518-03-01 23:00] Guard #3 begins shift\n\
+             [1518-03-02 00:10] Guard #4 begins shift\n\
+             [1518-12-31 23:59] Guard #5 begins shift\n",
+        )
+        .unwrap();
+        let dates: Vec<String> = shifts.iter().map(|s| s.date.to_string()).collect();
+        assert_eq!(
+            dates,
+            vec![
+                "1518-03-01",
+                "1518-03-01",
+                "1518-03-02",
+                "1518-03-02",
+                "1519-01-01"
+            ]
+        );
+    }
+
+    #[test]
+    fn malformed_logs() {
+        // Lines are numbered as they appear in the file, before sorting
+        assert_eq!(
+            error(
+                "[1518-11-01 00:10] Guard #10 begins shift\n\
+                 [1518-11-01 00:05] falls asleep\n"
+            ),
+            "Error: line 2: guard falls asleep before any shift begins"
+        );
+        assert_eq!(
+            error(
+                "[1518-11-01 00:00] Guard #10 begins shift\n\
+                 [1518-11-01 00:05] wakes up\n"
+            ),
+            "Error: line 2: guard wakes up without falling asleep"
+        );
+        assert_eq!(
+            error(
+                "[1518-11-01 00:20] falls asleep\n\
+                 [1518-11-01 00:00] Guard #10 begins shift\n\
+                 [1518-11-01 00:05] falls asleep\n"
+            ),
+            "Error: line 1: guard falls asleep while already asleep"
+        );
+        assert_eq!(
+            error(
+                "[1518-11-01 00:00] Guard #10 begins shift\n\
+                 [1518-11-01 00:05] falls asleep\n"
+            ),
+            "Error: line 2: guard falls asleep but never wakes up"
+        );
+        assert_eq!(
+            error(
+                "[1518-11-01 00:00] Guard #10 begins shift\n\
+                 [1518-11-01 00:05] falls asleep\n\
+                 [1518-11-02 00:00] Guard #99 begins shift\n"
+            ),
+            "Error: line 2: guard falls asleep but never wakes up"
+        );
+        assert_eq!(
+            error("[1518-11-01 00:00] Guard #10 begins shift\nnonsense\n"),
+            "Error: line 2: malformed log entry"
+        );
+    }
 }
@@ -0,0 +1,139 @@
+use super::{Shift, MINUTES_PER_DAY};
+use std::collections::BTreeMap;
+
+#[derive(Debug, Clone)]
+struct GuardStats {
+    total_minutes: usize,
+    per_minute: Vec<usize>,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct SleepStats {
+    guards: BTreeMap<usize, GuardStats>,
+}
+
+impl SleepStats {
+    pub fn from_shifts<'a, I: IntoIterator<Item = &'a Shift>>(shifts: I) -> SleepStats {
+        let mut guards = BTreeMap::new();
+        for shift in shifts {
+            let stats = guards.entry(shift.guard).or_insert_with(|| GuardStats {
+                total_minutes: 0,
+                per_minute: vec![0; MINUTES_PER_DAY],
+            });
+            for nap in shift.naps.iter() {
+                for minute in nap.clone() {
+                    stats.per_minute[minute.rem_euclid(MINUTES_PER_DAY as i64) as usize] += 1;
+                }
+                stats.total_minutes += (nap.end - nap.start) as usize;
+            }
+        }
+        return SleepStats { guards };
+    }
+
+    pub fn guards(&self) -> impl Iterator<Item = usize> + '_ {
+        return self.guards.keys().cloned();
+    }
+
+    pub fn total_minutes(&self, guard: usize) -> usize {
+        return self
+            .guards
+            .get(&guard)
+            .map_or(0, |stats| stats.total_minutes);
+    }
+
+    // Number of shifts the guard was asleep during each minute of the day
+    pub fn minute_histogram(&self, guard: usize) -> Option<&[usize]> {
+        return self
+            .guards
+            .get(&guard)
+            .map(|stats| stats.per_minute.as_slice());
+    }
+
+    // Minute of the day the guard is most often asleep, and how often.  Ties go to the earliest
+    // minute.
+    pub fn most_likely_minute(&self, guard: usize) -> Option<(usize, usize)> {
+        let histogram = self.minute_histogram(guard)?;
+        let (minute, &count) = histogram
+            .iter()
+            .enumerate()
+            .max_by_key(|&(minute, &count)| (count, std::cmp::Reverse(minute)))?;
+        return if count == 0 {
+            None
+        } else {
+            Some((minute, count))
+        };
+    }
+
+    // Guards sorted by total minutes asleep, sleepiest first
+    pub fn top_sleepiest(&self, n: usize) -> Vec<(usize, usize)> {
+        let mut result: Vec<(usize, usize)> = self
+            .guards
+            .iter()
+            .map(|(&guard, stats)| (guard, stats.total_minutes))
+            .collect();
+        result.sort_by_key(|&(guard, minutes)| (std::cmp::Reverse(minutes), guard));
+        result.truncate(n);
+        return result;
+    }
+}
+
+pub trait Strategy {
+    fn name(&self) -> &'static str;
+
+    fn description(&self) -> &'static str;
+
+    // Returns the chosen guard and minute
+    fn select(&self, stats: &SleepStats) -> Option<(usize, usize)>;
+}
+
+// Strategy 1: the guard with the most minutes asleep, at their most likely minute
+pub struct MostMinutesAsleep;
+
+impl Strategy for MostMinutesAsleep {
+    fn name(&self) -> &'static str {
+        "most-minutes"
+    }
+
+    fn description(&self) -> &'static str {
+        "guard with the most total minutes asleep, at their most likely minute"
+    }
+
+    fn select(&self, stats: &SleepStats) -> Option<(usize, usize)> {
+        let &(guard, _) = stats.top_sleepiest(1).first()?;
+        let (minute, _) = stats.most_likely_minute(guard)?;
+        return Some((guard, minute));
+    }
+}
+
+// Strategy 2: the guard most frequently asleep on the same minute
+pub struct MostFrequentMinute;
+
+impl Strategy for MostFrequentMinute {
+    fn name(&self) -> &'static str {
+        "most-frequent-minute"
+    }
+
+    fn description(&self) -> &'static str {
+        "guard most frequently asleep on any single minute"
+    }
+
+    fn select(&self, stats: &SleepStats) -> Option<(usize, usize)> {
+        let mut best: Option<(usize, usize, usize)> = None;
+        for guard in stats.guards() {
+            if let Some((minute, count)) = stats.most_likely_minute(guard) {
+                if best.is_none_or(|(_, _, best_count)| count > best_count) {
+                    best = Some((guard, minute, count));
+                }
+            }
+        }
+        return best.map(|(guard, minute, _)| (guard, minute));
+    }
+}
+
+pub fn strategies() -> Vec<Box<dyn Strategy>> {
+    return vec![Box::new(MostMinutesAsleep), Box::new(MostFrequentMinute)];
+}
+
+pub fn find_strategy(name: &str) -> Option<Box<dyn Strategy>> {
+    return strategies().into_iter().find(|s| s.name() == name);
+}
@@ -1,11 +1,69 @@
+#![allow(clippy::needless_return)]
+
+mod rules;
+mod trace;
+
+use parselib::GenericError;
+use rules::ReactionRules;
 use std::cmp;
+use std::thread;
+use std::time::Instant;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Polymer {
+    units: Vec<u8>,
+}
+
+impl Polymer {
+    fn from_bytes(input: &[u8], rules: &ReactionRules) -> Result<Polymer, GenericError> {
+        let start = input
+            .iter()
+            .position(|x| !x.is_ascii_whitespace())
+            .unwrap_or(input.len());
+        let end = input
+            .iter()
+            .rposition(|x| !x.is_ascii_whitespace())
+            .map_or(start, |i| i + 1);
+        let units = &input[start..end];
+        if let Some(i) = units.iter().position(|&x| !rules.is_unit(x)) {
+            return Err(GenericError::new(&format!(
+                "invalid unit '{}' at position {}",
+                units[i].escape_ascii(),
+                start + i
+            )));
+        }
+        return Ok(Polymer {
+            units: units.to_vec(),
+        });
+    }
+
+    fn units(&self) -> &[u8] {
+        return &self.units;
+    }
+}
+
+fn reduce<I: IntoIterator<Item = u8>>(input: I, rules: &ReactionRules) -> Vec<u8> {
+    let mut stack: Vec<u8> = Vec::new();
+    for unit in input {
+        match stack.last() {
+            Some(&top) if rules.reacts(top, unit) => {
+                stack.pop();
+            }
+            _ => stack.push(unit),
+        }
+    }
+    return stack;
+}
+
+fn reduced_length(polymer: &Polymer, rules: &ReactionRules) -> usize {
+    return reduce(polymer.units().iter().cloned(), rules).len();
+}
 
-fn reduced_length(mut input: Vec<u8>) -> usize {
+// The original quadratic reducer, kept as a baseline for the benchmark
+fn reduced_length_naive(mut input: Vec<u8>, rules: &ReactionRules) -> usize {
     let mut index = 0;
-    while index < input.len() - 1 {
-        assert!(input[index] < 128);
-        assert!(input[index + 1] < 128);
-        if (input[index] as i8 - input[index + 1] as i8).abs() == 32 {
+    while index + 1 < input.len() {
+        if rules.reacts(input[index], input[index + 1]) {
             input.remove(index);
             input.remove(index);
             index = if index == 0 { index } else { index - 1 };
@@ -16,21 +74,244 @@ fn reduced_length(mut input: Vec<u8>) -> usize {
     return input.len();
 }
 
-fn part2(input: &[u8]) -> usize {
+fn part2(polymer: &Polymer, rules: &ReactionRules) -> usize {
+    // Removing a unit type and then reducing gives the same result as removing it from the
+    // already reduced polymer, which is usually much shorter.  That only holds when the result
+    // of a reduction doesn't depend on the order the reactions happen in.
+    let reduced = reduce(polymer.units().iter().cloned(), rules);
+    let start = if rules.is_confluent() {
+        reduced.as_slice()
+    } else {
+        polymer.units()
+    };
+    return thread::scope(|scope| {
+        let handles: Vec<_> = rules
+            .unit_types(polymer.units())
+            .into_iter()
+            .map(|unit_type| {
+                scope.spawn(move || {
+                    reduce(
+                        start.iter().cloned().filter(|x| !unit_type.contains(x)),
+                        rules,
+                    )
+                    .len()
+                })
+            })
+            .collect();
+        handles
+            .into_iter()
+            .map(|handle| handle.join().unwrap())
+            .fold(reduced.len(), cmp::min)
+    });
+}
+
+fn part2_naive(input: &[u8], rules: &ReactionRules) -> usize {
     let mut min_length = input.len();
-    for char_to_remove in 'a' as u8..'z' as u8 {
+    for unit_type in rules.unit_types(input) {
         let reduced_polymer = input
             .iter()
             .cloned()
-            .filter(|x| (x | 0x20) != char_to_remove)
+            .filter(|x| !unit_type.contains(x))
             .collect();
-        min_length = cmp::min(min_length, reduced_length(reduced_polymer));
+        min_length = cmp::min(min_length, reduced_length_naive(reduced_polymer, rules));
     }
     return min_length;
 }
 
-fn main() {
-    let input = parselib::load_bytes("input.txt").expect("Failed to parse input");
-    println!("part1: {}", reduced_length(input.clone()));
-    println!("part2: {}", part2(input.as_slice()));
+// Random polymer biased towards reacting with the previous unit so that reductions cascade
+fn synthetic_polymer(length: usize, seed: u64) -> Polymer {
+    let mut random = parselib::Random::new(seed);
+    let mut result: Vec<u8> = Vec::with_capacity(length);
+    while result.len() < length {
+        let bits = random.next_u64();
+        let unit = match result.last() {
+            Some(&last) if bits.is_multiple_of(3) => last ^ 0x20,
+            _ => (b'a' + (bits >> 8) as u8 % 26) ^ if bits & 0x80 != 0 { 0x20 } else { 0 },
+        };
+        result.push(unit);
+    }
+    return Polymer { units: result };
+}
+
+fn bench(lengths: &[usize]) {
+    let rules = ReactionRules::case_pairs();
+    for &length in lengths {
+        let polymer = synthetic_polymer(length, length as u64);
+
+        let start = Instant::now();
+        let fast = (reduced_length(&polymer, &rules), part2(&polymer, &rules));
+        let fast_time = start.elapsed();
+
+        let start = Instant::now();
+        let naive = (
+            reduced_length_naive(polymer.units().to_vec(), &rules),
+            part2_naive(polymer.units(), &rules),
+        );
+        let naive_time = start.elapsed();
+
+        assert_eq!(fast, naive);
+        println!(
+            "{:>9} units: {}",
+            length,
+            parselib::compare_times("stack", fast_time, "naive", naive_time)
+        );
+    }
+}
+
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let mut rules = ReactionRules::case_pairs();
+    let mut trace_mode = None;
+    let mut trace_limit = 1000;
+    let mut iter = args.iter();
+    while let Some(arg) = iter.next() {
+        match arg.as_str() {
+            "bench" => {
+                bench(&[1_000, 10_000, 50_000, 100_000]);
+                return Ok(());
+            }
+            "trace" | "trace-json" => trace_mode = Some(arg.as_str()),
+            "--limit" => {
+                trace_limit = iter
+                    .next()
+                    .and_then(|limit| limit.parse().ok())
+                    .ok_or_else(|| GenericError::new("expected a number after --limit"))?;
+            }
+            "--rules" => {
+                let path = iter
+                    .next()
+                    .ok_or_else(|| GenericError::new("missing path for --rules"))?;
+                rules = ReactionRules::load(path)?;
+            }
+            _ => {
+                return Err(Box::new(GenericError::new(&format!(
+                    "unknown option {}",
+                    arg
+                ))))
+            }
+        }
+    }
+
+    let polymer = Polymer::from_bytes(&parselib::load_bytes("input.txt")?, &rules)?;
+    if let Some(mode) = trace_mode {
+        let trace = trace::trace_reduction(&polymer, &rules, trace_limit);
+        if mode == "trace-json" {
+            println!("{}", trace::to_json(&trace));
+            return Ok(());
+        }
+        let steps = trace::replay(&polymer, &trace);
+        println!("{:>6}  {}", 0, steps[0]);
+        for (i, (reaction, step)) in trace.reactions.iter().zip(&steps[1..]).enumerate() {
+            println!(
+                "{:>6}  {}{} at {}, {}: {}",
+                i + 1,
+                reaction.left as char,
+                reaction.right as char,
+                reaction.left_position,
+                reaction.right_position,
+                step
+            );
+        }
+        if trace.truncated {
+            println!("... stopped recording after {} reactions", trace_limit);
+        }
+        println!(
+            "{} units reduced to {}",
+            trace.initial_length, trace.final_length
+        );
+        return Ok(());
+    }
+    println!("part1: {}", reduced_length(&polymer, &rules));
+    println!("part2: {}", part2(&polymer, &rules));
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn polymer(input: &str) -> Polymer {
+        return Polymer::from_bytes(input.as_bytes(), &ReactionRules::case_pairs()).unwrap();
+    }
+
+    fn answers(polymer: &Polymer, rules: &ReactionRules) -> (usize, usize) {
+        let answers = (reduced_length(polymer, rules), part2(polymer, rules));
+        assert_eq!(
+            answers,
+            (
+                reduced_length_naive(polymer.units().to_vec(), rules),
+                part2_naive(polymer.units(), rules)
+            )
+        );
+        return answers;
+    }
+
+    #[test]
+    fn puzzle_example() {
+        let rules = ReactionRules::case_pairs();
+        assert_eq!(answers(&polymer("dabAcCaCBAcCcaDA"), &rules), (10, 4));
+    }
+
+    #[test]
+    fn surrounding_whitespace_is_trimmed() {
+        assert_eq!(polymer("dabAcCaCBAcCcaDA\n").units(), b"dabAcCaCBAcCcaDA");
+        assert_eq!(polymer("  aA\r\n\n").units(), b"aA");
+    }
+
+    #[test]
+    fn empty_input() {
+        let rules = ReactionRules::case_pairs();
+        assert_eq!(answers(&polymer(""), &rules), (0, 0));
+        assert_eq!(answers(&polymer("\n"), &rules), (0, 0));
+    }
+
+    #[test]
+    fn invalid_units_are_reported_by_position() {
+        let rules = ReactionRules::case_pairs();
+        let error = |input: &[u8]| Polymer::from_bytes(input, &rules).unwrap_err().to_string();
+        assert_eq!(
+            error("\nabé".as_bytes()),
+            "Error: invalid unit '\\xc3' at position 3"
+        );
+        assert_eq!(error(b"ab cd"), "Error: invalid unit ' ' at position 2");
+        assert_eq!(
+            error(b"ab\x00"),
+            "Error: invalid unit '\\x00' at position 2"
+        );
+    }
+
+    #[test]
+    fn every_unit_type_is_tried() {
+        // Only removing the last type lets everything else react
+        let rules = ReactionRules::case_pairs();
+        assert_eq!(answers(&polymer("abczCBA"), &rules), (7, 0));
+        assert_eq!(answers(&polymer("aZzbBAz"), &rules), (1, 0));
+    }
+
+    #[test]
+    fn units_without_rules_are_inert() {
+        let rules = ReactionRules::parse("+ -\n").unwrap();
+        let polymer = Polymer::from_bytes(b"x+-", &rules).unwrap();
+        assert_eq!(answers(&polymer, &rules), (1, 0));
+        let polymer = Polymer::from_bytes(b"+x-+-", &rules).unwrap();
+        assert_eq!(answers(&polymer, &rules), (3, 0));
+    }
+
+    #[test]
+    fn matches_naive_reducer() {
+        let rules = ReactionRules::case_pairs();
+        for seed in 1..50 {
+            answers(&synthetic_polymer(seed as usize * 7, seed), &rules);
+        }
+        // Not confluent, so part 2 can't work from the reduced polymer
+        let rules = ReactionRules::parse("aA\naB\nbB\n").unwrap();
+        for seed in 1..50 {
+            let units: Vec<u8> = synthetic_polymer(seed as usize * 7, seed)
+                .units()
+                .iter()
+                .map(|&x| b"aAbB"[x as usize % 4])
+                .collect();
+            answers(&Polymer { units }, &rules);
+        }
+    }
 }
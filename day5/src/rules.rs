@@ -0,0 +1,224 @@
+use parselib::GenericError;
+use std::path::Path;
+
+const NUM_UNITS: usize = 256;
+
+// Which pairs of adjacent units annihilate each other.  Pairs are unordered, so declaring that
+// `a` reacts with `A` also means `A` reacts with `a`.
+#[derive(Debug, Clone)]
+pub struct ReactionRules {
+    table: Vec<bool>,
+    alphabet: [bool; NUM_UNITS],
+}
+
+impl ReactionRules {
+    pub fn empty() -> ReactionRules {
+        return ReactionRules {
+            table: vec![false; NUM_UNITS * NUM_UNITS],
+            alphabet: [false; NUM_UNITS],
+        };
+    }
+
+    // Each lowercase ASCII letter reacts with its uppercase counterpart, as in the puzzle
+    pub fn case_pairs() -> ReactionRules {
+        return ReactionRules::from_pairs((b'a'..=b'z').map(|x| (x, x.to_ascii_uppercase())));
+    }
+
+    pub fn from_pairs<I: IntoIterator<Item = (u8, u8)>>(pairs: I) -> ReactionRules {
+        let mut rules = ReactionRules::empty();
+        for (a, b) in pairs {
+            rules.add_pair(a, b);
+        }
+        return rules;
+    }
+
+    // One rule per line, each naming the two units that annihilate, e.g. `aA` or `+ -`.  Lines
+    // starting with `#` are comments.
+    pub fn parse(text: &str) -> Result<ReactionRules, GenericError> {
+        let mut rules = ReactionRules::empty();
+        for (i, line) in text.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let units: Vec<u8> = line.bytes().filter(|x| !x.is_ascii_whitespace()).collect();
+            match units.as_slice() {
+                &[a, b] if a.is_ascii_graphic() && b.is_ascii_graphic() => rules.add_pair(a, b),
+                _ => {
+                    return Err(GenericError::new(&format!(
+                        "line {}: expected a pair of ASCII units, found {:?}",
+                        i + 1,
+                        line
+                    )))
+                }
+            }
+        }
+        return Ok(rules);
+    }
+
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<ReactionRules, GenericError> {
+        let text = parselib::load_text_file(path)
+            .map_err(|_| GenericError::new("could not read reaction rules"))?;
+        return ReactionRules::parse(&text);
+    }
+
+    pub fn add_pair(&mut self, a: u8, b: u8) {
+        self.table[a as usize * NUM_UNITS + b as usize] = true;
+        self.table[b as usize * NUM_UNITS + a as usize] = true;
+        self.alphabet[a as usize] = true;
+        self.alphabet[b as usize] = true;
+    }
+
+    pub fn reacts(&self, a: u8, b: u8) -> bool {
+        return self.table[a as usize * NUM_UNITS + b as usize];
+    }
+
+    // Any printable ASCII character can be a unit.  Units no rule mentions never react, but can
+    // still keep others apart.
+    pub fn is_unit(&self, unit: u8) -> bool {
+        return unit.is_ascii_graphic() || self.alphabet[unit as usize];
+    }
+
+    fn partners(&self, unit: u8) -> impl Iterator<Item = u8> + '_ {
+        return (0..NUM_UNITS)
+            .filter(move |&other| self.table[unit as usize * NUM_UNITS + other])
+            .map(|other| other as u8);
+    }
+
+    // When every unit has at most one partner the final polymer doesn't depend on the order
+    // reactions happen in, so units can be removed after reducing instead of before
+    pub fn is_confluent(&self) -> bool {
+        return (0..NUM_UNITS).all(|unit| self.partners(unit as u8).nth(1).is_none());
+    }
+
+    // Groups of units linked by reactions, e.g. `a` and `A` for the case rules, along with any
+    // units in `units` that don't react with anything, each in a group of its own
+    pub fn unit_types(&self, units: &[u8]) -> Vec<Vec<u8>> {
+        let mut present = self.alphabet;
+        for &unit in units {
+            present[unit as usize] = true;
+        }
+        let mut type_of = [usize::MAX; NUM_UNITS];
+        let mut types: Vec<Vec<u8>> = Vec::new();
+        for start in 0..NUM_UNITS {
+            if !present[start] || type_of[start] != usize::MAX {
+                continue;
+            }
+            let mut members = Vec::new();
+            let mut pending = vec![start as u8];
+            type_of[start] = types.len();
+            while let Some(unit) = pending.pop() {
+                members.push(unit);
+                for other in self.partners(unit) {
+                    if type_of[other as usize] == usize::MAX {
+                        type_of[other as usize] = types.len();
+                        pending.push(other);
+                    }
+                }
+            }
+            members.sort();
+            types.push(members);
+        }
+        return types;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashSet;
+
+    #[test]
+    fn parse_rules() {
+        let rules = ReactionRules::parse("# charges\n+ -\n\n  aB\n").unwrap();
+        assert!(rules.reacts(b'+', b'-') && rules.reacts(b'-', b'+'));
+        assert!(rules.reacts(b'B', b'a'));
+        assert!(!rules.reacts(b'a', b'A'));
+        assert!(!rules.reacts(b'+', b'+'));
+    }
+
+    #[test]
+    fn parse_errors() {
+        let error = |text: &str| ReactionRules::parse(text).unwrap_err().to_string();
+        assert_eq!(
+            error("aA\nabc\n"),
+            "Error: line 2: expected a pair of ASCII units, found \"abc\""
+        );
+        assert_eq!(
+            error("# one unit\na\n"),
+            "Error: line 2: expected a pair of ASCII units, found \"a\""
+        );
+        assert_eq!(
+            error("aA\n\né e\n"),
+            "Error: line 3: expected a pair of ASCII units, found \"é e\""
+        );
+    }
+
+    #[test]
+    fn unit_types() {
+        let types = ReactionRules::case_pairs().unit_types(b"");
+        assert_eq!(types.len(), 26);
+        assert_eq!(types[0], b"Aa");
+        assert_eq!(types[25], b"Zz");
+
+        // Chains of rules join into one type, and units without rules are types of their own
+        let rules = ReactionRules::parse("ab\nbc\n+-\n").unwrap();
+        assert_eq!(
+            rules.unit_types(b"xa+x"),
+            vec![b"+-".to_vec(), b"abc".to_vec(), b"x".to_vec()]
+        );
+    }
+
+    #[test]
+    fn confluence() {
+        assert!(ReactionRules::case_pairs().is_confluent());
+        assert!(ReactionRules::empty().is_confluent());
+        assert!(ReactionRules::parse("+-\nxy\n").unwrap().is_confluent());
+        // `b` can react with either neighbour in `abc`, leaving `c` or `a`
+        assert!(!ReactionRules::parse("ab\nbc\n").unwrap().is_confluent());
+        // `a` can react with the `a` or the `b` in `aab`, leaving `b` or `a`
+        assert!(!ReactionRules::parse("aa\nab\n").unwrap().is_confluent());
+    }
+
+    // Every polymer a sequence of reactions can end with, trying each possible order
+    fn all_results(units: &[u8], rules: &ReactionRules, results: &mut HashSet<Vec<u8>>) {
+        let mut reacted = false;
+        for i in 1..units.len() {
+            if rules.reacts(units[i - 1], units[i]) {
+                reacted = true;
+                let mut rest = units[..i - 1].to_vec();
+                rest.extend_from_slice(&units[i + 1..]);
+                all_results(&rest, rules, results);
+            }
+        }
+        if !reacted {
+            results.insert(units.to_vec());
+        }
+    }
+
+    #[test]
+    fn confluent_rules_have_one_result() {
+        let rule_sets = [
+            "aA\nbB\n",
+            "ab\ncc\n",
+            "ab\nbc\n",
+            "aa\nab\n",
+            "ab\nba\nc+\n",
+        ];
+        let mut random = parselib::Random::new(5);
+        for text in rule_sets.iter() {
+            let rules = ReactionRules::parse(text).unwrap();
+            let mut ambiguous = false;
+            for _ in 0..200 {
+                let length = random.below(11) as usize;
+                let units: Vec<u8> = (0..length)
+                    .map(|_| b"aAbBc+"[random.below(6) as usize])
+                    .collect();
+                let mut results = HashSet::new();
+                all_results(&units, &rules, &mut results);
+                ambiguous |= results.len() > 1;
+            }
+            assert_eq!(ambiguous, !rules.is_confluent(), "{:?}", text);
+        }
+    }
+}
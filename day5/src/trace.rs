@@ -0,0 +1,187 @@
+use super::rules::ReactionRules;
+use super::Polymer;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Reaction {
+    // Positions of the two units in the original polymer
+    pub left_position: usize,
+    pub right_position: usize,
+    pub left: u8,
+    pub right: u8,
+}
+
+#[derive(Debug, Clone)]
+pub struct Trace {
+    pub initial_length: usize,
+    pub final_length: usize,
+    pub reactions: Vec<Reaction>,
+    // Set when more than `limit` reactions happened and the rest weren't recorded
+    pub truncated: bool,
+}
+
+// Same reduction as `reduce`, but keeps track of where each unit came from so that every
+// reaction can be recorded.  Only the first `limit` reactions are kept.
+pub fn trace_reduction(polymer: &Polymer, rules: &ReactionRules, limit: usize) -> Trace {
+    let mut stack: Vec<(usize, u8)> = Vec::new();
+    let mut reactions = Vec::new();
+    let mut truncated = false;
+    for (position, &unit) in polymer.units().iter().enumerate() {
+        match stack.last() {
+            Some(&(top_position, top)) if rules.reacts(top, unit) => {
+                stack.pop();
+                if reactions.len() < limit {
+                    reactions.push(Reaction {
+                        left_position: top_position,
+                        right_position: position,
+                        left: top,
+                        right: unit,
+                    });
+                } else {
+                    truncated = true;
+                }
+            }
+            _ => stack.push((position, unit)),
+        }
+    }
+    return Trace {
+        initial_length: polymer.units().len(),
+        final_length: stack.len(),
+        reactions,
+        truncated,
+    };
+}
+
+// Polymer after each recorded reaction, starting with the original
+pub fn replay(polymer: &Polymer, trace: &Trace) -> Vec<String> {
+    let mut present = vec![true; polymer.units().len()];
+    let render = |present: &[bool]| -> String {
+        polymer
+            .units()
+            .iter()
+            .zip(present.iter())
+            .filter(|&(_, &p)| p)
+            .map(|(&unit, _)| unit as char)
+            .collect()
+    };
+
+    let mut result = vec![render(&present)];
+    for reaction in trace.reactions.iter() {
+        present[reaction.left_position] = false;
+        present[reaction.right_position] = false;
+        result.push(render(&present));
+    }
+    return result;
+}
+
+fn json_string(units: &[u8]) -> String {
+    let mut result = String::from("\"");
+    for &unit in units {
+        match unit {
+            b'"' => result.push_str("\\\""),
+            b'\\' => result.push_str("\\\\"),
+            _ => result.push(unit as char),
+        }
+    }
+    result.push('"');
+    return result;
+}
+
+pub fn to_json(trace: &Trace) -> String {
+    let reactions: Vec<String> = trace
+        .reactions
+        .iter()
+        .enumerate()
+        .map(|(i, reaction)| {
+            format!(
+                "{{\"step\":{},\"positions\":[{},{}],\"units\":{}}}",
+                i + 1,
+                reaction.left_position,
+                reaction.right_position,
+                json_string(&[reaction.left, reaction.right])
+            )
+        })
+        .collect();
+    return format!(
+        "{{\"initial_length\":{},\"final_length\":{},\"truncated\":{},\"reactions\":[{}]}}",
+        trace.initial_length,
+        trace.final_length,
+        trace.truncated,
+        reactions.join(",")
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn example() -> (Polymer, ReactionRules) {
+        let rules = ReactionRules::case_pairs();
+        let polymer = Polymer::from_bytes(b"dabAcCaCBAcCcaDA", &rules).unwrap();
+        return (polymer, rules);
+    }
+
+    #[test]
+    fn puzzle_example() {
+        let (polymer, rules) = example();
+        let trace = trace_reduction(&polymer, &rules, 1000);
+        let positions: Vec<(usize, usize)> = trace
+            .reactions
+            .iter()
+            .map(|r| (r.left_position, r.right_position))
+            .collect();
+        assert_eq!(positions, vec![(4, 5), (3, 6), (10, 11)]);
+        assert_eq!(trace.reactions[1].left, b'A');
+        assert_eq!(trace.reactions[1].right, b'a');
+        assert_eq!((trace.initial_length, trace.final_length), (16, 10));
+        assert!(!trace.truncated);
+        assert_eq!(
+            replay(&polymer, &trace),
+            vec![
+                "dabAcCaCBAcCcaDA",
+                "dabAaCBAcCcaDA",
+                "dabCBAcCcaDA",
+                "dabCBAcaDA"
+            ]
+        );
+    }
+
+    #[test]
+    fn limit() {
+        let (polymer, rules) = example();
+        assert!(!trace_reduction(&polymer, &rules, 3).truncated);
+
+        let trace = trace_reduction(&polymer, &rules, 2);
+        assert!(trace.truncated);
+        assert_eq!(trace.reactions.len(), 2);
+        // The lengths still cover the whole reduction
+        assert_eq!(trace.final_length, 10);
+        assert_eq!(replay(&polymer, &trace).last().unwrap(), "dabCBAcCcaDA");
+
+        let trace = trace_reduction(&polymer, &rules, 0);
+        assert!(trace.truncated);
+        assert_eq!(replay(&polymer, &trace), vec!["dabAcCaCBAcCcaDA"]);
+    }
+
+    #[test]
+    fn json() {
+        let (polymer, rules) = example();
+        assert_eq!(
+            to_json(&trace_reduction(&polymer, &rules, 2)),
+            concat!(
+                r#"{"initial_length":16,"final_length":10,"truncated":true,"reactions":["#,
+                r#"{"step":1,"positions":[4,5],"units":"cC"},"#,
+                r#"{"step":2,"positions":[3,6],"units":"Aa"}]}"#
+            )
+        );
+
+        let rules = ReactionRules::parse("\"\\\n").unwrap();
+        let polymer = Polymer::from_bytes(b"x\"\\", &rules).unwrap();
+        assert_eq!(
+            to_json(&trace_reduction(&polymer, &rules, 10)),
+            concat!(
+                r#"{"initial_length":3,"final_length":1,"truncated":false,"reactions":["#,
+                r#"{"step":1,"positions":[1,2],"units":"\"\\"}]}"#
+            )
+        );
+    }
+}
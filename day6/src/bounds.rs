@@ -0,0 +1,89 @@
+use super::metric::Position;
+use std::cmp;
+
+// Inclusive axis-aligned box of grid cells in any number of dimensions
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Bounds {
+    pub min: Vec<isize>,
+    pub max: Vec<isize>,
+}
+
+impl Bounds {
+    // Smallest box containing every position, or None if there are none
+    pub fn of(positions: &[Position]) -> Option<Bounds> {
+        let first = positions.first()?;
+        let mut bounds = Bounds {
+            min: first.clone(),
+            max: first.clone(),
+        };
+        for position in positions {
+            for (axis, &v) in position.iter().enumerate() {
+                bounds.min[axis] = cmp::min(bounds.min[axis], v);
+                bounds.max[axis] = cmp::max(bounds.max[axis], v);
+            }
+        }
+        return Some(bounds);
+    }
+
+    pub fn dimensions(&self) -> usize {
+        return self.min.len();
+    }
+
+    pub fn extent(&self, axis: usize) -> usize {
+        return (self.max[axis] - self.min[axis] + 1) as usize;
+    }
+
+    pub fn num_cells(&self) -> usize {
+        return (0..self.dimensions())
+            .map(|axis| self.extent(axis))
+            .product();
+    }
+
+    pub fn expanded(&self, by: isize) -> Bounds {
+        return Bounds {
+            min: self.min.iter().map(|v| v - by).collect(),
+            max: self.max.iter().map(|v| v + by).collect(),
+        };
+    }
+
+    // Row-major index of a cell, with the last axis varying fastest
+    pub fn index(&self, point: &[isize]) -> usize {
+        let mut index = 0;
+        for (axis, &v) in point.iter().enumerate() {
+            index = index * self.extent(axis) + (v - self.min[axis]) as usize;
+        }
+        return index;
+    }
+
+    pub fn point(&self, index: usize) -> Position {
+        let mut point = vec![0; self.dimensions()];
+        self.point_into(index, &mut point);
+        return point;
+    }
+
+    pub fn point_into(&self, mut index: usize, point: &mut [isize]) {
+        for axis in (0..self.dimensions()).rev() {
+            let extent = self.extent(axis);
+            point[axis] = self.min[axis] + (index % extent) as isize;
+            index /= extent;
+        }
+    }
+
+    pub fn cells(&self) -> impl Iterator<Item = Position> + '_ {
+        return (0..self.num_cells()).map(move |index| self.point(index));
+    }
+
+    // Cells on the outside of the box.  Cells on an edge or corner are repeated.
+    pub fn surface(&self) -> Vec<Position> {
+        let mut result = Vec::new();
+        for axis in 0..self.dimensions() {
+            for &side in &[self.min[axis], self.max[axis]] {
+                let mut face = self.clone();
+                face.min[axis] = side;
+                face.max[axis] = side;
+                result.extend(face.cells());
+            }
+        }
+        return result;
+    }
+}
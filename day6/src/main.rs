@@ -1,114 +1,560 @@
-use parselib::ParseError;
+#![allow(clippy::needless_return)]
+
+use parselib::{GenericError, ParseError};
 use std::cmp;
+use std::collections::VecDeque;
+use std::time::Instant;
 
-fn parse_line(line: &str) -> Result<(isize, isize), ParseError> {
-    let mut iter = line.split(", ");
-    let mut next_int = || {
-        iter.next()
-            .ok_or(ParseError)?
-            .parse()
-            .map_err(|_| ParseError)
-    };
+mod bounds;
+mod metric;
 
-    return Ok((next_int()?, next_int()?));
+use bounds::Bounds;
+use metric::{Metric, Position};
+
+fn parse_line(line: &str) -> Result<Position, ParseError> {
+    return line
+        .split(',')
+        .map(|v| v.trim().parse().map_err(|_| ParseError))
+        .collect();
 }
 
-fn find_unique_closest(position: (isize, isize), positions: &[(isize, isize)]) -> Option<usize> {
-    let mut min_dist = isize::max_value();
-    let mut min_dist_index = 0;
-    let mut num_min_dist = 0;
-    for (i, (x, y)) in positions.into_iter().enumerate() {
-        let dist = (x - position.0).abs() + (y - position.1).abs();
-        if dist < min_dist {
-            min_dist_index = i;
-            min_dist = dist;
-            num_min_dist = 1;
-        } else if dist == min_dist {
-            num_min_dist += 1;
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Owner {
+    Unvisited,
+    Unique(usize),
+    Tied,
+}
+
+// Unique closest position for every cell of the inclusive bounding box, indexed by
+// `Bounds::index`.  Computed with a breadth-first search out from every position at once, which
+// visits each cell a constant number of times.  Metrics that can't be searched that way fall
+// back to checking every position for every cell.
+fn closest_map(positions: &[Position], metric: &dyn Metric) -> Vec<Option<usize>> {
+    return match Bounds::of(positions) {
+        Some(bounds) => closest_map_in(positions, metric, &bounds),
+        None => Vec::new(),
+    };
+}
+
+// Same as `closest_map` for any box containing every position
+fn closest_map_in(
+    positions: &[Position],
+    metric: &dyn Metric,
+    bounds: &Bounds,
+) -> Vec<Option<usize>> {
+    let steps = match metric.grid_steps(bounds.dimensions()) {
+        Some(steps) => steps,
+        None => {
+            return bounds
+                .cells()
+                .map(|cell| metric::find_unique_closest(metric, &cell, positions))
+                .collect()
+        }
+    };
+    let mut owners = vec![Owner::Unvisited; bounds.num_cells()];
+    let mut distances = vec![usize::MAX; bounds.num_cells()];
+    let mut queue = VecDeque::new();
+
+    for (i, position) in positions.iter().enumerate() {
+        let cell = bounds.index(position);
+        owners[cell] = match owners[cell] {
+            Owner::Unvisited => {
+                distances[cell] = 0;
+                queue.push_back(cell);
+                Owner::Unique(i)
+            }
+            _ => Owner::Tied,
+        };
+    }
+
+    // Change in cell index for each step, valid whenever the step stays inside the box
+    let mut stride = 1;
+    let mut offsets = vec![0isize; steps.len()];
+    for axis in (0..bounds.dimensions()).rev() {
+        for (offset, step) in offsets.iter_mut().zip(steps.iter()) {
+            *offset += step[axis] * stride;
+        }
+        stride *= bounds.extent(axis) as isize;
+    }
+
+    let mut point = vec![0; bounds.dimensions()];
+    while let Some(cell) = queue.pop_front() {
+        bounds.point_into(cell, &mut point);
+        let owner = owners[cell];
+        let next_distance = distances[cell] + 1;
+        for (step, &offset) in steps.iter().zip(offsets.iter()) {
+            let inside = point
+                .iter()
+                .zip(step.iter())
+                .enumerate()
+                .all(|(axis, (v, d))| bounds.min[axis] <= v + d && v + d <= bounds.max[axis]);
+            if !inside {
+                continue;
+            }
+            let neighbor = (cell as isize + offset) as usize;
+            if distances[neighbor] == usize::MAX {
+                distances[neighbor] = next_distance;
+                owners[neighbor] = owner;
+                queue.push_back(neighbor);
+            } else if distances[neighbor] == next_distance && owners[neighbor] != owner {
+                // Reached at the same distance from somewhere else
+                owners[neighbor] = Owner::Tied;
+            }
         }
     }
-    return if num_min_dist == 1 {
-        Some(min_dist_index)
-    } else {
-        None
+
+    return owners
+        .into_iter()
+        .map(|owner| match owner {
+            Owner::Unique(i) => Some(i),
+            _ => None,
+        })
+        .collect();
+}
+
+fn closest_map_brute_force(positions: &[Position], metric: &dyn Metric) -> Vec<Option<usize>> {
+    return match Bounds::of(positions) {
+        Some(bounds) => bounds
+            .cells()
+            .map(|cell| metric::find_unique_closest(metric, &cell, positions))
+            .collect(),
+        None => Vec::new(),
     };
 }
 
-fn grid_size(positions: &[(isize, isize)]) -> (isize, isize, isize, isize) {
-    let mut row_min = isize::max_value();
-    let mut row_max = isize::min_value();
-    let mut col_min = isize::max_value();
-    let mut col_max = isize::min_value();
-    for &(row, col) in positions {
-        row_min = cmp::min(row, row_min);
-        row_max = cmp::max(row, row_max);
-        col_min = cmp::min(col, col_min);
-        col_max = cmp::max(col, col_max);
-    }
-    return (row_min, row_max, col_min, col_max);
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Region {
+    area: usize,
+    infinite: bool,
 }
 
-fn part1(positions: &[(isize, isize)]) -> isize {
-    let (min_row, max_row, min_col, max_col) = grid_size(positions);
-    let mut grid = Vec::with_capacity(((max_row - min_row) * (max_col - min_col)) as usize);
+// Area closest to each position, and whether it extends forever.
+//
+// Finite regions under Manhattan distance always lie inside the bounding box, but under other
+// metrics they can reach outside it.  Regions are connected, so the box is grown until no finite
+// region reaches its surface, at which point each one is entirely inside.
+fn classify_regions(
+    positions: &[Position],
+    metric: &dyn Metric,
+) -> Result<Vec<Region>, GenericError> {
+    let base = match Bounds::of(positions) {
+        Some(bounds) => bounds,
+        None => return Ok(Vec::new()),
+    };
+    let infinite = metric.unbounded_regions(positions)?;
+    let mut margin = 0;
+    loop {
+        let bounds = base.expanded(margin);
+        let owners = closest_map_in(positions, metric, &bounds);
+        let escapes = bounds
+            .surface()
+            .iter()
+            .any(|cell| owners[bounds.index(cell)].is_some_and(|owner| !infinite[owner]));
+        if escapes {
+            margin = cmp::max(1, margin * 2);
+            continue;
+        }
 
-    for row in min_row..max_row {
-        for col in min_col..max_col {
-            grid.push(find_unique_closest((row, col), positions).map_or(-1, |i| i as i32));
+        let mut regions: Vec<Region> = infinite
+            .iter()
+            .map(|&infinite| Region { area: 0, infinite })
+            .collect();
+        for &i in owners.iter().flatten() {
+            regions[i].area += 1;
         }
+        return Ok(regions);
     }
+}
+
+fn part1(positions: &[Position], metric: &dyn Metric) -> Result<Option<usize>, GenericError> {
+    return Ok(classify_regions(positions, metric)?
+        .iter()
+        .filter(|region| !region.infinite)
+        .map(|region| region.area)
+        .max());
+}
 
-    let mut areas = vec![0isize; positions.len()];
-    for &v in grid.iter() {
-        if v >= 0 {
-            areas[v as usize] += 1;
+fn total_distance(position: &[isize], positions: &[Position], metric: &dyn Metric) -> isize {
+    return positions
+        .iter()
+        .map(|other| metric.distance(position, other))
+        .sum();
+}
+
+// Smallest integer in lo..=hi where `f` is less than `threshold`, given that `f` is below the
+// threshold at `hi` and only decreases from lo to hi
+fn first_below<F: Fn(isize) -> isize>(
+    mut lo: isize,
+    mut hi: isize,
+    threshold: isize,
+    f: F,
+) -> isize {
+    while lo < hi {
+        let mid = lo + (hi - lo) / 2;
+        if f(mid) < threshold {
+            hi = mid;
+        } else {
+            lo = mid + 1;
         }
     }
+    return lo;
+}
 
-    let num_cols = (max_col - min_col) as usize;
-    for &v in grid.iter().take(num_cols) {
-        if v >= 0 {
-            areas[v as usize] = 0;
+// Total distance along one axis from a coordinate to every position's coordinate on that axis
+struct AxisTotal {
+    sorted: Vec<isize>,
+    // Sum of the first `i` sorted coordinates at index `i`
+    prefix: Vec<isize>,
+}
+
+impl AxisTotal {
+    fn new(mut coordinates: Vec<isize>) -> AxisTotal {
+        coordinates.sort_unstable();
+        let mut prefix = Vec::with_capacity(coordinates.len() + 1);
+        prefix.push(0);
+        for &c in coordinates.iter() {
+            prefix.push(prefix[prefix.len() - 1] + c);
         }
+        return AxisTotal {
+            sorted: coordinates,
+            prefix,
+        };
+    }
+
+    // Total at `v`, given that it comes after the first `below` sorted coordinates and before the
+    // rest
+    fn total_between(&self, v: isize, below: usize) -> isize {
+        let n = self.sorted.len();
+        return (2 * below as isize - n as isize) * v + self.prefix[n] - 2 * self.prefix[below];
     }
-    for &v in grid.iter().rev().take(num_cols) {
-        if v >= 0 {
-            areas[v as usize] = 0;
+
+    fn total(&self, v: isize) -> isize {
+        return self.total_between(v, self.sorted.partition_point(|&c| c <= v));
+    }
+
+    // Smallest coordinate where the total is less than `budget`, if there is one.  The total is
+    // piecewise linear between the sorted coordinates and falls until the median, so this finds
+    // the first coordinate under budget and then how far the piece before it stays under.
+    fn first_below(&self, budget: isize) -> Option<isize> {
+        let n = self.sorted.len();
+        let at = |i: usize| self.total_between(self.sorted[i], i);
+        let median = n / 2;
+        if n == 0 || at(median) >= budget {
+            return None;
         }
+        let i = first_below(0, median as isize, budget, |i| at(i as usize)) as usize;
+        let slope = (n - 2 * i) as isize;
+        return Some(self.sorted[i] - (budget - at(i) - 1) / slope);
     }
-    for &v in grid.iter().step_by(num_cols) {
-        if v >= 0 {
-            areas[v as usize] = 0;
+}
+
+// Manhattan distance adds up separately along each axis, so the total for a cell is the part from
+// the other axes, which is fixed for a line, plus the part along the line, whose run below the
+// remaining budget comes straight from the sorted coordinates
+fn separable_safe_cells(
+    positions: &[Position],
+    lines: &Bounds,
+    last_axis: usize,
+    threshold: isize,
+) -> usize {
+    let axes: Vec<AxisTotal> = (0..last_axis)
+        .map(|axis| AxisTotal::new(positions.iter().map(|p| p[axis]).collect()))
+        .collect();
+    let along = AxisTotal::new(positions.iter().map(|p| p[last_axis]).collect());
+    let reversed = AxisTotal::new(positions.iter().map(|p| -p[last_axis]).collect());
+
+    let mut result = 0;
+    for cell in lines.cells() {
+        let budget = threshold
+            - axes
+                .iter()
+                .zip(cell.iter())
+                .map(|(axis, &v)| axis.total(v))
+                .sum::<isize>();
+        if let (Some(first), Some(last)) = (along.first_below(budget), reversed.first_below(budget))
+        {
+            result += (-last - first + 1) as usize;
         }
     }
-    for &v in grid.iter().skip(num_cols - 1).step_by(num_cols) {
-        if v >= 0 {
-            areas[v as usize] = 0;
+    return result;
+}
+
+// Number of cells whose total distance to every position is less than the threshold, or None if
+// there are infinitely many.  Total distance is convex, so the safe cells in each line along the
+// last axis form a single run around that line's minimum, which is found with binary searches
+// instead of checking every cell.
+fn part2(positions: &[Position], metric: &dyn Metric, threshold: isize) -> Option<usize> {
+    let bounds = match Bounds::of(positions) {
+        Some(bounds) => bounds,
+        None => return if threshold > 0 { None } else { Some(0) },
+    };
+
+    // Every position adds at least the difference in each coordinate to the total, so nothing
+    // further than this outside the bounding box can be safe
+    let reach = cmp::max(threshold, 0) / positions.len() as isize + 1;
+    let search = bounds.expanded(reach);
+    let last_axis = bounds.dimensions() - 1;
+    let (lo, hi) = (search.min[last_axis], search.max[last_axis]);
+    let lines = Bounds {
+        min: search.min[..last_axis].to_vec(),
+        max: search.max[..last_axis].to_vec(),
+    };
+    if metric.separable() {
+        return Some(separable_safe_cells(
+            positions, &lines, last_axis, threshold,
+        ));
+    }
+
+    let mut result = 0;
+    for mut cell in lines.cells() {
+        cell.push(0);
+        let line_total = |v| {
+            let mut cell = cell.clone();
+            cell[last_axis] = v;
+            total_distance(&cell, positions, metric)
+        };
+        // First cell where the total stops decreasing
+        let best = first_below(lo, hi, 1, |v| {
+            if line_total(v + 1) >= line_total(v) {
+                0
+            } else {
+                1
+            }
+        });
+        if line_total(best) >= threshold {
+            continue;
         }
+        let first = first_below(lo, best, threshold, line_total);
+        let last = -first_below(-hi, -best, threshold, |v| line_total(-v));
+        result += (last - first + 1) as usize;
     }
+    return Some(result);
+}
 
-    return *areas.iter().max().unwrap();
+fn random_positions(count: usize, extent: isize, seed: u64) -> Vec<Position> {
+    let mut random = parselib::Random::new(seed);
+    let mut next_coordinate = || random.below(extent as u64) as isize;
+    return (0..count)
+        .map(|_| vec![next_coordinate(), next_coordinate()])
+        .collect();
 }
 
-fn part2(positions: &[(isize, isize)]) -> isize {
-    let (min_row, max_row, min_col, max_col) = grid_size(positions);
-    let mut result = 0;
-    for row in min_row..max_row {
-        for col in min_col..max_col {
-            let total_dist: isize = positions
-                .iter()
-                .map(|&(pos_row, pos_col)| (row - pos_row).abs() + (col - pos_col).abs())
-                .sum();
-            if total_dist < 10000 {
-                result += 1;
+fn bench() {
+    for &(count, extent) in &[(50, 400), (500, 1000), (2000, 1000), (5000, 1000)] {
+        let positions = random_positions(count, extent, count as u64);
+
+        let start = Instant::now();
+        let flood = closest_map(&positions, &metric::Manhattan);
+        let flood_time = start.elapsed();
+
+        let start = Instant::now();
+        let brute_force = closest_map_brute_force(&positions, &metric::Manhattan);
+        let brute_force_time = start.elapsed();
+
+        assert!(flood == brute_force);
+        println!(
+            "{:>5} points, {}x{} grid: {}",
+            count,
+            extent,
+            extent,
+            parselib::compare_times("flood fill", flood_time, "brute force", brute_force_time)
+        );
+    }
+}
+
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let mut metric: Box<dyn Metric> = Box::new(metric::Manhattan);
+    let mut threshold = 10000;
+    let mut iter = args.iter();
+    while let Some(arg) = iter.next() {
+        match arg.as_str() {
+            "bench" => {
+                bench();
+                return Ok(());
+            }
+            "--metric" => {
+                let name = iter.next().map_or("", String::as_str);
+                metric = metric::find_metric(name).ok_or_else(|| {
+                    GenericError::new(&format!(
+                        "unknown metric {:?} (expected manhattan, chebyshev or euclidean)",
+                        name
+                    ))
+                })?;
+            }
+            "--threshold" => {
+                threshold = iter
+                    .next()
+                    .and_then(|threshold| threshold.parse().ok())
+                    .ok_or_else(|| GenericError::new("expected a number after --threshold"))?;
+            }
+            _ => {
+                return Err(Box::new(GenericError::new(&format!(
+                    "unknown option {}",
+                    arg
+                ))))
             }
         }
     }
-    return result;
+
+    let input = parselib::parse_lines_fn("input.txt", parse_line)?;
+    if let Some(first) = input.first() {
+        if let Some(line) = input.iter().position(|p| p.len() != first.len()) {
+            return Err(Box::new(GenericError::new(&format!(
+                "line {} has {} coordinates but line 1 has {}",
+                line + 1,
+                input[line].len(),
+                first.len()
+            ))));
+        }
+    }
+
+    match part1(input.as_slice(), metric.as_ref())? {
+        Some(area) => println!("part 1: {}", area),
+        None => println!("part 1: no finite regions"),
+    }
+    match part2(input.as_slice(), metric.as_ref(), threshold) {
+        Some(size) => println!("part 2: {}", size),
+        None => println!("part 2: unbounded"),
+    }
+    Ok(())
 }
 
-fn main() {
-    let input = parselib::parse_lines_fn("input.txt", parse_line).expect("Could not parse input");
-    println!("part 1: {}", part1(input.as_slice()));
-    println!("part 2: {}", part2(input.as_slice()));
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn points(coordinates: &[(isize, isize)]) -> Vec<Position> {
+        return coordinates.iter().map(|&(x, y)| vec![x, y]).collect();
+    }
+
+    fn puzzle_example() -> Vec<Position> {
+        return points(&[(1, 1), (1, 6), (8, 3), (3, 4), (5, 5), (8, 9)]);
+    }
+
+    #[test]
+    fn puzzle_example_answers() {
+        assert_eq!(
+            part1(&puzzle_example(), &metric::Manhattan).unwrap(),
+            Some(17)
+        );
+        assert_eq!(part2(&puzzle_example(), &metric::Manhattan, 32), Some(16));
+    }
+
+    #[test]
+    fn single_point_has_no_finite_regions() {
+        for metric in metric::metrics() {
+            let regions = classify_regions(&points(&[(3, 4)]), metric.as_ref()).unwrap();
+            assert_eq!(
+                regions,
+                vec![Region {
+                    area: 1,
+                    infinite: true
+                }]
+            );
+            assert_eq!(part1(&points(&[(3, 4)]), metric.as_ref()).unwrap(), None);
+        }
+    }
+
+    #[test]
+    fn collinear_points() {
+        let diagonal = points(&[(0, 0), (1, 1), (2, 2)]);
+        let manhattan = classify_regions(&diagonal, &metric::Manhattan).unwrap();
+        assert_eq!(
+            manhattan.iter().map(|r| r.infinite).collect::<Vec<_>>(),
+            vec![true, false, true]
+        );
+        assert_eq!(part1(&diagonal, &metric::Manhattan).unwrap(), Some(1));
+        assert_eq!(part1(&diagonal, &metric::Chebyshev).unwrap(), None);
+        assert_eq!(part1(&diagonal, &metric::SquaredEuclidean).unwrap(), None);
+
+        // The middle of a row owns a strip reaching forever across it, except under Chebyshev
+        // where far enough across the row every point is the same distance away
+        let row = points(&[(0, 0), (3, 0), (7, 0)]);
+        assert_eq!(part1(&row, &metric::Manhattan).unwrap(), None);
+        assert_eq!(part1(&row, &metric::SquaredEuclidean).unwrap(), None);
+        assert_eq!(part1(&row, &metric::Chebyshev).unwrap(), Some(13));
+    }
+
+    #[test]
+    fn chebyshev_regions_need_two_dimensions() {
+        let line = vec![vec![0], vec![3], vec![7]];
+        assert_eq!(part1(&line, &metric::Chebyshev).unwrap(), Some(3));
+        let cube = vec![vec![0, 0, 0], vec![4, 4, 4], vec![2, 2, 2]];
+        assert!(part1(&cube, &metric::Chebyshev).is_err());
+    }
+
+    #[test]
+    fn no_points() {
+        assert_eq!(part1(&[], &metric::Manhattan).unwrap(), None);
+        assert!(closest_map(&[], &metric::Manhattan).is_empty());
+    }
+
+    #[test]
+    fn flood_fill_matches_brute_force() {
+        let mut cases = vec![
+            puzzle_example(),
+            points(&[(0, 0), (1, 1), (2, 2)]),
+            points(&[(0, 0), (0, 0), (4, 2)]),
+        ];
+        for seed in 1..20 {
+            cases.push(random_positions(2 + seed as usize % 12, 15, seed));
+        }
+        for positions in cases.iter() {
+            for metric in metric::metrics() {
+                assert_eq!(
+                    closest_map(positions, metric.as_ref()),
+                    closest_map_brute_force(positions, metric.as_ref()),
+                    "{} {:?}",
+                    metric.name(),
+                    positions
+                );
+            }
+        }
+    }
+
+    fn safe_cells_brute_force(
+        positions: &[Position],
+        metric: &dyn Metric,
+        threshold: isize,
+    ) -> usize {
+        let reach = threshold / positions.len() as isize + 1;
+        return Bounds::of(positions)
+            .unwrap()
+            .expanded(reach)
+            .cells()
+            .filter(|cell| total_distance(cell, positions, metric) < threshold)
+            .count();
+    }
+
+    #[test]
+    fn part2_matches_brute_force() {
+        let mut cases = vec![
+            puzzle_example(),
+            points(&[(3, 4)]),
+            points(&[(0, 0), (0, 0), (4, 2)]),
+            vec![vec![0, 0, 0], vec![2, -1, 5], vec![4, 3, 1], vec![1, 1, 1]],
+        ];
+        for seed in 1..12 {
+            cases.push(random_positions(1 + seed as usize % 8, 10, seed));
+        }
+        for positions in cases.iter() {
+            for metric in metric::metrics() {
+                for &threshold in [0, 1, 5, 17, 40, 90].iter() {
+                    assert_eq!(
+                        part2(positions, metric.as_ref(), threshold),
+                        Some(safe_cells_brute_force(
+                            positions,
+                            metric.as_ref(),
+                            threshold
+                        )),
+                        "{} {} {:?}",
+                        metric.name(),
+                        threshold,
+                        positions
+                    );
+                }
+            }
+        }
+    }
 }
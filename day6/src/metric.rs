@@ -0,0 +1,354 @@
+use super::bounds::Bounds;
+use parselib::GenericError;
+
+pub type Position = Vec<isize>;
+
+pub trait Metric {
+    fn name(&self) -> &'static str;
+
+    // Must be convex and at least the difference in any one coordinate
+    fn distance(&self, a: &[isize], b: &[isize]) -> isize;
+
+    // Steps such that a breadth-first search over the grid finds this distance, if there are any
+    fn grid_steps(&self, dimensions: usize) -> Option<Vec<Position>>;
+
+    // Whether the set of cells closest to each position reaches arbitrarily far away, or an error
+    // if there's no exact test for these positions
+    fn unbounded_regions(&self, positions: &[Position]) -> Result<Vec<bool>, GenericError>;
+
+    // Whether the distance is the sum of the distances along each axis
+    fn separable(&self) -> bool;
+}
+
+pub fn find_unique_closest<M: Metric + ?Sized>(
+    metric: &M,
+    position: &[isize],
+    positions: &[Position],
+) -> Option<usize> {
+    let mut min_dist = isize::MAX;
+    let mut min_dist_index = 0;
+    let mut num_min_dist = 0;
+    for (i, other) in positions.iter().enumerate() {
+        let dist = metric.distance(position, other);
+        if dist < min_dist {
+            min_dist_index = i;
+            min_dist = dist;
+            num_min_dist = 1;
+        } else if dist == min_dist {
+            num_min_dist += 1;
+        }
+    }
+    return if num_min_dist == 1 {
+        Some(min_dist_index)
+    } else {
+        None
+    };
+}
+
+fn owners_of<M: Metric + ?Sized, I: IntoIterator<Item = Position>>(
+    metric: &M,
+    cells: I,
+    positions: &[Position],
+) -> Vec<bool> {
+    let mut result = vec![false; positions.len()];
+    for cell in cells {
+        if let Some(i) = find_unique_closest(metric, &cell, positions) {
+            result[i] = true;
+        }
+    }
+    return result;
+}
+
+// Under Manhattan distance, or any of these metrics in one dimension, stepping away from a face
+// of the bounding box gets further from every position by the same amount, so a region is
+// unbounded exactly when it reaches the surface of the box
+fn unbounded_from_surface<M: Metric + ?Sized>(metric: &M, positions: &[Position]) -> Vec<bool> {
+    return match Bounds::of(positions) {
+        Some(bounds) => owners_of(metric, bounds.surface(), positions),
+        None => Vec::new(),
+    };
+}
+
+fn unit_steps(dimensions: usize) -> Vec<Position> {
+    let mut result = Vec::new();
+    for axis in 0..dimensions {
+        for &delta in &[-1, 1] {
+            let mut step = vec![0; dimensions];
+            step[axis] = delta;
+            result.push(step);
+        }
+    }
+    return result;
+}
+
+pub struct Manhattan;
+
+impl Metric for Manhattan {
+    fn name(&self) -> &'static str {
+        "manhattan"
+    }
+
+    fn distance(&self, a: &[isize], b: &[isize]) -> isize {
+        return a.iter().zip(b).map(|(x, y)| (x - y).abs()).sum();
+    }
+
+    fn grid_steps(&self, dimensions: usize) -> Option<Vec<Position>> {
+        return Some(unit_steps(dimensions));
+    }
+
+    fn unbounded_regions(&self, positions: &[Position]) -> Result<Vec<bool>, GenericError> {
+        return Ok(unbounded_from_surface(self, positions));
+    }
+
+    fn separable(&self) -> bool {
+        return true;
+    }
+}
+
+pub struct Chebyshev;
+
+impl Metric for Chebyshev {
+    fn name(&self) -> &'static str {
+        "chebyshev"
+    }
+
+    fn distance(&self, a: &[isize], b: &[isize]) -> isize {
+        return a
+            .iter()
+            .zip(b)
+            .map(|(x, y)| (x - y).abs())
+            .max()
+            .unwrap_or(0);
+    }
+
+    // Every combination of -1, 0 and 1 along each axis, except not moving at all
+    fn grid_steps(&self, dimensions: usize) -> Option<Vec<Position>> {
+        let cube = Bounds {
+            min: vec![-1; dimensions],
+            max: vec![1; dimensions],
+        };
+        return Some(
+            cube.cells()
+                .filter(|step| step.iter().any(|&v| v != 0))
+                .collect(),
+        );
+    }
+
+    // In two dimensions, diagonal coordinates u = x + y, v = x - y make the distance half the
+    // Manhattan distance, and diagonal steps move u or v by 2 while getting further from every
+    // position beyond the box by the same amount.  A region is unbounded exactly when it reaches
+    // the two-cell-thick border of the box in those coordinates.
+    //
+    // There's no such change of coordinates in three or more dimensions, where the unit ball is
+    // a cube rather than a cross-polytope, and no exact test here.
+    fn unbounded_regions(&self, positions: &[Position]) -> Result<Vec<bool>, GenericError> {
+        let dimensions = positions.first().map_or(0, |p| p.len());
+        if dimensions < 2 {
+            return Ok(unbounded_from_surface(self, positions));
+        } else if dimensions > 2 {
+            return Err(GenericError::new(&format!(
+                "can't tell which chebyshev regions are infinite in {} dimensions",
+                dimensions
+            )));
+        }
+        let diagonal: Vec<Position> = positions
+            .iter()
+            .map(|p| vec![p[0] + p[1], p[0] - p[1]])
+            .collect();
+        let bounds = Bounds::of(&diagonal).unwrap();
+        let mut border = bounds.expanded(1).surface();
+        border.extend(bounds.surface());
+        let cells = border
+            .into_iter()
+            .filter(|uv| (uv[0] + uv[1]) % 2 == 0)
+            .map(|uv| vec![(uv[0] + uv[1]) / 2, (uv[0] - uv[1]) / 2]);
+        return Ok(owners_of(self, cells, positions));
+    }
+
+    fn separable(&self) -> bool {
+        return false;
+    }
+}
+
+pub struct SquaredEuclidean;
+
+// Determinant of a square integer matrix by fraction-free Gaussian elimination
+fn determinant(mut matrix: Vec<Vec<i128>>) -> i128 {
+    let n = matrix.len();
+    let mut sign = 1;
+    let mut previous_pivot = 1;
+    for k in 0..n {
+        let pivot_row = match (k..n).find(|&row| matrix[row][k] != 0) {
+            Some(row) => row,
+            None => return 0,
+        };
+        if pivot_row != k {
+            matrix.swap(pivot_row, k);
+            sign = -sign;
+        }
+        for row in k + 1..n {
+            for col in k + 1..n {
+                matrix[row][col] = (matrix[row][col] * matrix[k][k]
+                    - matrix[row][k] * matrix[k][col])
+                    / previous_pivot;
+            }
+        }
+        previous_pivot = matrix[k][k];
+    }
+    return sign * if n == 0 { 1 } else { matrix[n - 1][n - 1] };
+}
+
+fn gcd(a: i128, b: i128) -> i128 {
+    return if b == 0 { a } else { gcd(b, a % b) };
+}
+
+fn rank(vectors: &[Vec<i128>]) -> usize {
+    let mut rows = vectors.to_vec();
+    let mut rank = 0;
+    let num_cols = rows.first().map_or(0, |row| row.len());
+    for col in 0..num_cols {
+        let pivot_row = match (rank..rows.len()).find(|&row| rows[row][col] != 0) {
+            Some(row) => row,
+            None => continue,
+        };
+        rows.swap(pivot_row, rank);
+        let (done, remaining) = rows.split_at_mut(rank + 1);
+        let pivot_row = &done[rank];
+        for row in remaining.iter_mut() {
+            let factor = row[col];
+            for (v, &p) in row.iter_mut().zip(pivot_row.iter()) {
+                *v = *v * pivot_row[col] - p * factor;
+            }
+            // Keep the entries small
+            let divisor = row.iter().fold(0, |a, &b| gcd(a, b.abs()));
+            if divisor > 1 {
+                row.iter_mut().for_each(|v| *v /= divisor);
+            }
+        }
+        rank += 1;
+    }
+    return rank;
+}
+
+// Vector perpendicular to n - 1 vectors in n dimensions, zero if they're linearly dependent
+fn normal(vectors: &[&Vec<i128>], dimensions: usize) -> Vec<i128> {
+    return (0..dimensions)
+        .map(|skip| {
+            let minor = vectors
+                .iter()
+                .map(|v| {
+                    (0..dimensions)
+                        .filter(|&axis| axis != skip)
+                        .map(|axis| v[axis])
+                        .collect()
+                })
+                .collect();
+            if skip % 2 == 0 {
+                determinant(minor)
+            } else {
+                -determinant(minor)
+            }
+        })
+        .collect();
+}
+
+// Calls `f` with every way of choosing `k` of the indices 0..n in increasing order, stopping
+// early if it returns true
+fn any_combination<F: FnMut(&[usize]) -> bool>(n: usize, k: usize, mut f: F) -> bool {
+    if k > n {
+        return false;
+    }
+    let mut chosen: Vec<usize> = (0..k).collect();
+    loop {
+        if f(&chosen) {
+            return true;
+        }
+        // Advance the rightmost index that can still move, then reset everything after it
+        let i = match (0..k).rev().find(|&i| chosen[i] < n - k + i) {
+            Some(i) => i,
+            None => return false,
+        };
+        chosen[i] += 1;
+        for j in i + 1..k {
+            chosen[j] = chosen[j - 1] + 1;
+        }
+    }
+}
+
+// Whether some direction doesn't lead towards any of the given offsets.  When the offsets span
+// every dimension such a direction can always be chosen perpendicular to n - 1 of them, so only
+// those need to be tried.
+fn has_supporting_direction(offsets: &[Vec<i128>], dimensions: usize) -> bool {
+    if rank(offsets) < dimensions {
+        return true;
+    }
+    return any_combination(offsets.len(), dimensions - 1, |chosen| {
+        let vectors: Vec<&Vec<i128>> = chosen.iter().map(|&i| &offsets[i]).collect();
+        let direction = normal(&vectors, dimensions);
+        if direction.iter().all(|&v| v == 0) {
+            return false;
+        }
+        let dots: Vec<i128> = offsets
+            .iter()
+            .map(|offset| {
+                offset
+                    .iter()
+                    .zip(direction.iter())
+                    .map(|(a, b)| a * b)
+                    .sum()
+            })
+            .collect();
+        return dots.iter().all(|&dot| dot <= 0) || dots.iter().all(|&dot| dot >= 0);
+    });
+}
+
+impl Metric for SquaredEuclidean {
+    fn name(&self) -> &'static str {
+        "euclidean"
+    }
+
+    fn distance(&self, a: &[isize], b: &[isize]) -> isize {
+        return a.iter().zip(b).map(|(x, y)| (x - y) * (x - y)).sum();
+    }
+
+    fn grid_steps(&self, _dimensions: usize) -> Option<Vec<Position>> {
+        return None;
+    }
+
+    // Moving from a position in direction u gets closer to another position q only if u points
+    // towards q, so a cell is unbounded exactly when some direction doesn't point towards any
+    // other position.  That includes positions in the middle of an edge of the convex hull,
+    // whose cells are infinite strips.
+    fn unbounded_regions(&self, positions: &[Position]) -> Result<Vec<bool>, GenericError> {
+        return Ok(positions
+            .iter()
+            .map(|p| {
+                let mut offsets: Vec<Vec<i128>> = positions
+                    .iter()
+                    .map(|q| q.iter().zip(p).map(|(a, b)| (a - b) as i128).collect())
+                    .collect();
+                offsets.sort();
+                offsets.dedup();
+                offsets.retain(|offset| offset.iter().any(|&v| v != 0));
+                let duplicated = positions.iter().filter(|&q| q == p).count() > 1;
+                !duplicated && has_supporting_direction(&offsets, p.len())
+            })
+            .collect());
+    }
+
+    fn separable(&self) -> bool {
+        return false;
+    }
+}
+
+pub fn metrics() -> Vec<Box<dyn Metric>> {
+    return vec![
+        Box::new(Manhattan),
+        Box::new(Chebyshev),
+        Box::new(SquaredEuclidean),
+    ];
+}
+
+pub fn find_metric(name: &str) -> Option<Box<dyn Metric>> {
+    return metrics().into_iter().find(|m| m.name() == name);
+}
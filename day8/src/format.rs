@@ -0,0 +1,398 @@
+use super::tree::Node;
+use parselib::GenericError;
+
+// Other ways of writing a license tree: the flat stream of numbers the puzzle uses, an indented
+// outline with one node per line, and JSON.  Like parsing the flat stream, these all walk the
+// tree with explicit stacks so that deep trees work.
+
+impl Node {
+    pub fn serialize(&self) -> Vec<usize> {
+        let mut result = Vec::new();
+        // Each node along with how many of its children have been written
+        let mut stack = vec![(self, 0)];
+        result.extend_from_slice(&[self.children.len(), self.metadata.len()]);
+        while let Some((node, next_child)) = stack.last_mut() {
+            let node: &Node = node;
+            if let Some(child) = node.children.get(*next_child) {
+                *next_child += 1;
+                result.extend_from_slice(&[child.children.len(), child.metadata.len()]);
+                stack.push((child, 0));
+            } else {
+                result.extend_from_slice(&node.metadata);
+                stack.pop();
+            }
+        }
+        return result;
+    }
+
+    pub fn serialize_string(&self) -> String {
+        let numbers: Vec<String> = self.serialize().iter().map(|n| n.to_string()).collect();
+        return numbers.join(" ");
+    }
+
+    // One node per line, indented two spaces per level, with its metadata in brackets followed
+    // by its value.  Lines are written in pre-order but a node's value is only known once its
+    // children are finished, so each line's value is filled in on the way back up.
+    pub fn pretty_print(&self) -> String {
+        // Depth, node and value for each line
+        let mut lines = vec![(0, self, 0)];
+        // Each node along with how many of its children have been visited and its line
+        let mut stack = vec![(self, 0, 0)];
+        let mut pending: Vec<usize> = Vec::new();
+        while let Some(&(node, next_child, line)) = stack.last() {
+            if let Some(child) = node.children.get(next_child) {
+                stack.last_mut().unwrap().1 += 1;
+                lines.push((stack.len(), child, 0));
+                stack.push((child, 0, lines.len() - 1));
+            } else {
+                let child_values = pending.split_off(pending.len() - node.children.len());
+                let value = node.value_from_children(&child_values);
+                lines[line].2 = value;
+                pending.push(value);
+                stack.pop();
+            }
+        }
+
+        let mut result = String::new();
+        for (depth, node, value) in lines {
+            let metadata: Vec<String> = node.metadata.iter().map(|m| m.to_string()).collect();
+            // Format widths are limited to 16 bits, which deep trees go past
+            result.push_str(&" ".repeat(depth * 2));
+            result.push_str(&format!("[{}]  value={}\n", metadata.join(" "), value));
+        }
+        return result;
+    }
+
+    // Reads the output of `pretty_print`.  Anything after the closing bracket on each line is
+    // ignored, and children just need to be indented further than their parent.
+    pub fn parse_outline(input: &str) -> Result<Node, GenericError> {
+        let error = |line: usize, message: &str| {
+            GenericError::new(&format!("line {}: {}", line + 1, message))
+        };
+
+        // Nodes that might still get more children, along with their indentation
+        let mut stack: Vec<(usize, Node)> = Vec::new();
+        let mut root = None;
+        for (i, line) in input.lines().enumerate() {
+            if line.trim().is_empty() {
+                continue;
+            }
+            let indent = line.len() - line.trim_start().len();
+            let content = line.trim_start();
+            let end = content.find(']').ok_or_else(|| error(i, "missing ]"))?;
+            if !content.starts_with('[') {
+                return Err(error(i, "expected [ at start of node"));
+            }
+            let metadata = parselib::parse_tokens(&content[1..end])
+                .map_err(|e| error(i, &format!("bad metadata {:?}", e.token)))?;
+
+            finish_outline_nodes(&mut stack, &mut root, indent);
+            if stack.is_empty() && root.is_some() {
+                return Err(error(i, "more than one root node"));
+            }
+            stack.push((
+                indent,
+                Node {
+                    children: Vec::new(),
+                    metadata,
+                },
+            ));
+        }
+        finish_outline_nodes(&mut stack, &mut root, 0);
+        return root.ok_or_else(|| GenericError::new("no nodes in outline"));
+    }
+
+    pub fn to_json(&self) -> String {
+        let mut result = String::new();
+        let mut stack = vec![(self, 0)];
+        result.push_str(&json_node_start(self));
+        while let Some((node, next_child)) = stack.last_mut() {
+            let node: &Node = node;
+            if let Some(child) = node.children.get(*next_child) {
+                if *next_child > 0 {
+                    result.push(',');
+                }
+                *next_child += 1;
+                result.push_str(&json_node_start(child));
+                stack.push((child, 0));
+            } else {
+                result.push_str("]}");
+                stack.pop();
+            }
+        }
+        return result;
+    }
+
+    // Reads objects of the form {"metadata": [1, 2], "children": [...]}, with the keys in either
+    // order and either one optional
+    pub fn parse_json(input: &str) -> Result<Node, GenericError> {
+        let mut lexer = JsonLexer {
+            input: input.as_bytes(),
+            position: 0,
+        };
+        lexer.expect(JsonToken::Punct(b'{'))?;
+        let mut stack = vec![Node::default()];
+        let mut state = JsonState::ObjectStart;
+        loop {
+            state = match state {
+                JsonState::ObjectStart => match lexer.next()? {
+                    JsonToken::Punct(b'}') => JsonState::ObjectEnd,
+                    token => parse_json_member(&mut lexer, &mut stack, token)?,
+                },
+                JsonState::AfterMember => match lexer.next()? {
+                    JsonToken::Punct(b',') => {
+                        let token = lexer.next()?;
+                        parse_json_member(&mut lexer, &mut stack, token)?
+                    }
+                    JsonToken::Punct(b'}') => JsonState::ObjectEnd,
+                    token => return Err(lexer.unexpected(&token)),
+                },
+                JsonState::ObjectEnd => {
+                    let node = stack.pop().unwrap();
+                    match stack.last_mut() {
+                        Some(parent) => {
+                            parent.children.push(node);
+                            JsonState::AfterChild
+                        }
+                        None => {
+                            return match lexer.next()? {
+                                JsonToken::End => Ok(node),
+                                token => Err(lexer.unexpected(&token)),
+                            };
+                        }
+                    }
+                }
+                JsonState::AfterChild => match lexer.next()? {
+                    JsonToken::Punct(b',') => {
+                        lexer.expect(JsonToken::Punct(b'{'))?;
+                        stack.push(Node::default());
+                        JsonState::ObjectStart
+                    }
+                    JsonToken::Punct(b']') => JsonState::AfterMember,
+                    token => return Err(lexer.unexpected(&token)),
+                },
+            };
+        }
+    }
+}
+
+// Attaches nodes indented at least as far as a new line to their parents, since they can't get
+// any more children
+fn finish_outline_nodes(stack: &mut Vec<(usize, Node)>, root: &mut Option<Node>, indent: usize) {
+    while stack
+        .last()
+        .is_some_and(|&(top_indent, _)| top_indent >= indent)
+    {
+        let (_, node) = stack.pop().unwrap();
+        match stack.last_mut() {
+            Some((_, parent)) => parent.children.push(node),
+            None => *root = Some(node),
+        }
+    }
+}
+
+fn json_node_start(node: &Node) -> String {
+    let metadata: Vec<String> = node.metadata.iter().map(|m| m.to_string()).collect();
+    return format!("{{\"metadata\":[{}],\"children\":[", metadata.join(","));
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum JsonToken {
+    Punct(u8),
+    Str(String),
+    Number(usize),
+    End,
+}
+
+enum JsonState {
+    ObjectStart,
+    AfterMember,
+    ObjectEnd,
+    AfterChild,
+}
+
+struct JsonLexer<'a> {
+    input: &'a [u8],
+    position: usize,
+}
+
+impl<'a> JsonLexer<'a> {
+    fn error(&self, message: &str) -> GenericError {
+        return GenericError::new(&format!("{} at byte {}", message, self.position));
+    }
+
+    fn unexpected(&self, token: &JsonToken) -> GenericError {
+        return self.error(&format!("unexpected {:?}", token));
+    }
+
+    fn next(&mut self) -> Result<JsonToken, GenericError> {
+        while self.position < self.input.len() && self.input[self.position].is_ascii_whitespace() {
+            self.position += 1;
+        }
+        let start = self.position;
+        let c = match self.input.get(start) {
+            Some(&c) => c,
+            None => return Ok(JsonToken::End),
+        };
+        self.position += 1;
+        return match c {
+            b'{' | b'}' | b'[' | b']' | b':' | b',' => Ok(JsonToken::Punct(c)),
+            b'"' => {
+                let length = self.input[self.position..]
+                    .iter()
+                    .position(|&c| c == b'"')
+                    .ok_or_else(|| self.error("unterminated string"))?;
+                let s = String::from_utf8_lossy(&self.input[self.position..self.position + length]);
+                self.position += length + 1;
+                Ok(JsonToken::Str(s.into_owned()))
+            }
+            b'0'..=b'9' => {
+                while self.position < self.input.len() && self.input[self.position].is_ascii_digit()
+                {
+                    self.position += 1;
+                }
+                let digits = std::str::from_utf8(&self.input[start..self.position]).unwrap();
+                digits
+                    .parse()
+                    .map(JsonToken::Number)
+                    .map_err(|_| self.error("number out of range"))
+            }
+            _ => Err(self.error(&format!("unexpected character {:?}", c as char))),
+        };
+    }
+
+    fn expect(&mut self, expected: JsonToken) -> Result<(), GenericError> {
+        let token = self.next()?;
+        return if token == expected {
+            Ok(())
+        } else {
+            Err(self.unexpected(&token))
+        };
+    }
+}
+
+fn parse_json_member(
+    lexer: &mut JsonLexer,
+    stack: &mut Vec<Node>,
+    key: JsonToken,
+) -> Result<JsonState, GenericError> {
+    let key = match key {
+        JsonToken::Str(key) => key,
+        token => return Err(lexer.unexpected(&token)),
+    };
+    lexer.expect(JsonToken::Punct(b':'))?;
+    lexer.expect(JsonToken::Punct(b'['))?;
+    match key.as_str() {
+        "metadata" => {
+            let mut metadata = Vec::new();
+            loop {
+                match lexer.next()? {
+                    JsonToken::Number(n) => metadata.push(n),
+                    JsonToken::Punct(b']') if metadata.is_empty() => break,
+                    token => return Err(lexer.unexpected(&token)),
+                }
+                match lexer.next()? {
+                    JsonToken::Punct(b',') => continue,
+                    JsonToken::Punct(b']') => break,
+                    token => return Err(lexer.unexpected(&token)),
+                }
+            }
+            stack.last_mut().unwrap().metadata = metadata;
+            return Ok(JsonState::AfterMember);
+        }
+        "children" => match lexer.next()? {
+            JsonToken::Punct(b']') => return Ok(JsonState::AfterMember),
+            JsonToken::Punct(b'{') => {
+                stack.push(Node::default());
+                return Ok(JsonState::ObjectStart);
+            }
+            token => return Err(lexer.unexpected(&token)),
+        },
+        _ => return Err(lexer.error(&format!("unknown key {:?}", key))),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn node(metadata: &[usize], children: Vec<Node>) -> Node {
+        return Node {
+            children,
+            metadata: metadata.to_vec(),
+        };
+    }
+
+    fn trees() -> Vec<Node> {
+        return vec![
+            node(&[], vec![]),
+            node(&[7], vec![]),
+            // The puzzle example
+            node(
+                &[1, 1, 2],
+                vec![
+                    node(&[10, 11, 12], vec![]),
+                    node(&[2], vec![node(&[99], vec![])]),
+                ],
+            ),
+            // Nodes without metadata, and leaves next to nodes with children
+            node(
+                &[3, 1],
+                vec![
+                    node(&[], vec![node(&[], vec![])]),
+                    node(&[4, 5], vec![]),
+                    node(&[], vec![node(&[1], vec![]), node(&[0, 0], vec![])]),
+                ],
+            ),
+        ];
+    }
+
+    fn assert_round_trips(root: &Node) {
+        assert!(Node::parse(&root.serialize()).unwrap() == *root, "flat");
+        assert!(
+            Node::parse_outline(&root.pretty_print()).unwrap() == *root,
+            "outline"
+        );
+        assert!(Node::parse_json(&root.to_json()).unwrap() == *root, "json");
+    }
+
+    #[test]
+    fn round_trips() {
+        for root in trees().iter() {
+            assert_round_trips(root);
+        }
+    }
+
+    // The outline indents every line by its depth, so its size grows with the square of the
+    // depth and it gets a shallower chain
+    #[test]
+    fn round_trips_deep_chains() {
+        let root = Node::parse(&crate::deep_chain(1_000_000)).unwrap();
+        assert!(Node::parse(&root.serialize()).unwrap() == root, "flat");
+        assert!(Node::parse_json(&root.to_json()).unwrap() == root, "json");
+        assert_round_trips(&Node::parse(&crate::deep_chain(2_000)).unwrap());
+    }
+
+    #[test]
+    fn pretty_print_shows_values() {
+        assert_eq!(
+            trees()[2].pretty_print(),
+            "[1 1 2]  value=66\n  [10 11 12]  value=33\n  [2]  value=0\n    [99]  value=99\n"
+        );
+    }
+
+    #[test]
+    fn serialize_matches_the_puzzle_format() {
+        assert_eq!(
+            trees()[2].serialize_string(),
+            "2 3 0 3 10 11 12 1 1 0 1 99 2 1 1 2"
+        );
+    }
+
+    #[test]
+    fn json_keys_in_any_order() {
+        let root = Node::parse_json(r#"{"children": [{"metadata": [4]}], "metadata": [1]}"#);
+        assert!(root.unwrap() == node(&[1], vec![node(&[4], vec![])]));
+        assert!(Node::parse_json(r#"{"metadata": [1]"#).is_err());
+    }
+}
@@ -0,0 +1,180 @@
+use parselib::Random;
+use std::ops::RangeInclusive;
+
+// Random license streams for checking the parser and evaluation against, along with the answers
+// worked out while generating rather than from a parsed tree
+
+#[derive(Debug, Clone)]
+pub struct TreeConfig {
+    pub seed: u64,
+    // Nodes at this depth are always leaves, where the root is at depth 0
+    pub max_depth: usize,
+    pub children: RangeInclusive<usize>,
+    pub metadata_len: RangeInclusive<usize>,
+    // Metadata for leaves
+    pub leaf_metadata: RangeInclusive<usize>,
+    // How far past the last child references from nodes with children can go, to cover references
+    // to children that don't exist
+    pub missing_references: usize,
+    // Once this many nodes have been started every further node is a leaf, so that wide deep
+    // configurations still finish
+    pub max_nodes: usize,
+}
+
+impl Default for TreeConfig {
+    fn default() -> TreeConfig {
+        return TreeConfig {
+            seed: 1,
+            max_depth: 6,
+            children: 0..=4,
+            metadata_len: 1..=3,
+            leaf_metadata: 1..=9,
+            missing_references: 1,
+            max_nodes: 100_000,
+        };
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct GeneratedTree {
+    pub numbers: Vec<usize>,
+    pub num_nodes: usize,
+    pub metadata_sum: usize,
+    pub root_value: usize,
+}
+
+fn in_range(random: &mut Random, range: &RangeInclusive<usize>) -> usize {
+    return random.range(*range.start() as i64, *range.end() as i64) as usize;
+}
+
+// A node whose header has been written but whose children aren't all finished
+struct Pending {
+    num_children: usize,
+    metadata_len: usize,
+    child_values: Vec<usize>,
+}
+
+pub fn generate(config: &TreeConfig) -> GeneratedTree {
+    let mut random = Random::new(config.seed);
+    let mut result = GeneratedTree {
+        numbers: Vec::new(),
+        num_nodes: 0,
+        metadata_sum: 0,
+        root_value: 0,
+    };
+    let mut stack: Vec<Pending> = Vec::new();
+    let mut start_node = true;
+    loop {
+        if start_node {
+            let num_children =
+                if stack.len() < config.max_depth && result.num_nodes < config.max_nodes {
+                    in_range(&mut random, &config.children)
+                } else {
+                    0
+                };
+            let metadata_len = in_range(&mut random, &config.metadata_len);
+            result
+                .numbers
+                .extend_from_slice(&[num_children, metadata_len]);
+            result.num_nodes += 1;
+            stack.push(Pending {
+                num_children,
+                metadata_len,
+                child_values: Vec::with_capacity(num_children),
+            });
+        }
+
+        let top = stack.last().unwrap();
+        if top.child_values.len() < top.num_children {
+            start_node = true;
+            continue;
+        }
+
+        let node = stack.pop().unwrap();
+        let mut value = 0;
+        for _ in 0..node.metadata_len {
+            let entry = if node.num_children == 0 {
+                let entry = in_range(&mut random, &config.leaf_metadata);
+                value += entry;
+                entry
+            } else {
+                let entry = in_range(
+                    &mut random,
+                    &(1..=node.num_children + config.missing_references),
+                );
+                value += node.child_values.get(entry - 1).unwrap_or(&0);
+                entry
+            };
+            result.numbers.push(entry);
+            result.metadata_sum += entry;
+        }
+        match stack.last_mut() {
+            Some(parent) => parent.child_values.push(value),
+            None => {
+                result.root_value = value;
+                return result;
+            }
+        }
+        start_node = false;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tree::Node;
+
+    // Parses and evaluates generated trees across a spread of shapes, checking against the
+    // answers worked out by the generator
+    #[test]
+    fn parsed_trees_match_the_generator() {
+        let shapes = [
+            TreeConfig::default(),
+            TreeConfig {
+                max_depth: 1,
+                children: 0..=20,
+                ..TreeConfig::default()
+            },
+            TreeConfig {
+                max_depth: 40,
+                children: 0..=2,
+                metadata_len: 0..=2,
+                ..TreeConfig::default()
+            },
+            TreeConfig {
+                max_depth: 8,
+                children: 1..=5,
+                metadata_len: 1..=6,
+                leaf_metadata: 0..=1000,
+                missing_references: 4,
+                max_nodes: 2000,
+                ..TreeConfig::default()
+            },
+        ];
+        for seed in 1..=200 {
+            for shape in shapes.iter() {
+                let config = TreeConfig {
+                    seed,
+                    ..shape.clone()
+                };
+                let generated = generate(&config);
+                let root = Node::parse(&generated.numbers).unwrap();
+                assert_eq!(root.sum_metadata(), generated.metadata_sum, "{:?}", config);
+                assert_eq!(root.value(), generated.root_value, "{:?}", config);
+                assert_eq!(root.num_nodes(), generated.num_nodes, "{:?}", config);
+                assert_eq!(root.serialize(), generated.numbers, "{:?}", config);
+            }
+        }
+    }
+
+    #[test]
+    fn same_seed_same_tree() {
+        let config = TreeConfig::default();
+        assert_eq!(generate(&config).numbers, generate(&config).numbers);
+        let other = TreeConfig {
+            seed: 2,
+            ..TreeConfig::default()
+        };
+        assert_ne!(generate(&config).numbers, generate(&other).numbers);
+    }
+}
@@ -1,50 +1,186 @@
-use std::iter::Iterator;
+#![allow(clippy::needless_return)]
+
+mod format;
+mod generate;
+mod tree;
+
+use generate::TreeConfig;
 use parselib::GenericError;
+use std::ops::RangeInclusive;
+use std::time::Instant;
+use tree::Node;
 
-fn compute_sum_metadata<Iter>(iter: &mut Iter) -> Result<usize, GenericError>
-where
-    Iter: Iterator<Item=usize>
-{
-    let num_children = iter.next().ok_or(GenericError::new("Could not get num children"))?;
-    let num_metadata = iter.next().ok_or(GenericError::new("Could not get num metadata"))?;
-    let mut sum_metadata: usize = 0;
-    for _ in 0..num_children {
-        sum_metadata += compute_sum_metadata(iter)?;
+// A chain of nodes each with one child, ending in a leaf, where every node has metadata 1
+fn deep_chain(depth: usize) -> Vec<usize> {
+    let mut result = Vec::with_capacity(depth * 3);
+    for _ in 1..depth {
+        result.extend_from_slice(&[1, 1]);
     }
-    sum_metadata += iter.take(num_metadata).sum::<usize>();
-    return Ok(sum_metadata);
-}
-
-fn compute_node_value<Iter>(iter: &mut Iter) -> Result<usize, GenericError>
-where
-    Iter: Iterator<Item=usize>
-{
-    let num_children = iter.next().ok_or(GenericError::new("Could not get num children"))?;
-    let num_metadata = iter.next().ok_or(GenericError::new("Could not get num metadata"))?;
-    let mut child_values = Vec::with_capacity(num_children);
-    for _ in 0..num_children {
-        child_values.push(compute_node_value(iter)?);
+    result.extend_from_slice(&[0, 1]);
+    result.resize(result.len() + depth, 1);
+    return result;
+}
+
+fn check_deep_chain(depth: usize) -> Result<(), Box<dyn std::error::Error>> {
+    let root = Node::parse(&deep_chain(depth))?;
+    let (sum, value, tree_depth) = (root.sum_metadata(), root.value(), root.depth());
+    println!(
+        "depth {}: metadata sum {}, value {}, measured depth {}",
+        depth, sum, value, tree_depth
+    );
+    if sum != depth || value != 1 || tree_depth != depth {
+        return Err(Box::new(GenericError::new(
+            "unexpected result for deep chain",
+        )));
     }
-    let node_value = if num_children == 0 {
-        iter.take(num_metadata).sum::<usize>()
-    } else {
-        let mut value = 0;
-        for m in iter.take(num_metadata) {
-            if m - 1 < num_children {
-                value += child_values[m - 1];
-            }
+    return Ok(());
+}
+
+// Reads a tree written as the flat stream of numbers, an indented outline or JSON
+fn parse_any(input: &str) -> Result<Node, Box<dyn std::error::Error>> {
+    return match input.trim_start().chars().next() {
+        Some('{') => Ok(Node::parse_json(input)?),
+        Some('[') => Ok(Node::parse_outline(input)?),
+        _ => Ok(Node::parse_str(input)?),
+    };
+}
+
+// Accepts either a single number or an inclusive range written as "lo-hi"
+fn parse_range(text: &str) -> Result<RangeInclusive<usize>, Box<dyn std::error::Error>> {
+    return match text.split_once('-') {
+        Some((lo, hi)) => Ok(lo.parse()?..=hi.parse()?),
+        None => {
+            let n = text.parse()?;
+            Ok(n..=n)
         }
-        value
     };
-    return Ok(node_value);
 }
 
-fn main() -> Result<(), Box<std::error::Error>> {
-    let mut input: Vec<usize> = Vec::new();
-    for num_str in parselib::load_text_file("input.txt")?.split(' ') {
-        input.push(num_str.parse()?);
+fn parse_tree_config(args: &[String]) -> Result<TreeConfig, Box<dyn std::error::Error>> {
+    let mut config = TreeConfig::default();
+    let mut iter = args.iter();
+    while let Some(arg) = iter.next() {
+        let value = iter
+            .next()
+            .ok_or_else(|| GenericError::new(&format!("expected a value after {}", arg)))?;
+        match arg.as_str() {
+            "--seed" => config.seed = value.parse()?,
+            "--depth" => config.max_depth = value.parse()?,
+            "--children" => config.children = parse_range(value)?,
+            "--metadata-len" => config.metadata_len = parse_range(value)?,
+            "--leaf-metadata" => config.leaf_metadata = parse_range(value)?,
+            "--missing-references" => config.missing_references = value.parse()?,
+            "--max-nodes" => config.max_nodes = value.parse()?,
+            _ => {
+                return Err(Box::new(GenericError::new(&format!(
+                    "unknown option {}",
+                    arg
+                ))))
+            }
+        }
+    }
+    return Ok(config);
+}
+
+fn bench() -> Result<(), Box<dyn std::error::Error>> {
+    for &max_nodes in &[10_000, 100_000, 1_000_000, 5_000_000] {
+        let config = TreeConfig {
+            max_depth: 30,
+            children: 1..=3,
+            max_nodes,
+            ..TreeConfig::default()
+        };
+        let start = Instant::now();
+        let generated = generate::generate(&config);
+        let generate_time = start.elapsed();
+
+        let start = Instant::now();
+        let root = Node::parse(&generated.numbers)?;
+        let parse_time = start.elapsed();
+
+        let start = Instant::now();
+        let sum = root.sum_metadata();
+        let sum_time = start.elapsed();
+
+        let start = Instant::now();
+        let value = root.value();
+        let value_time = start.elapsed();
+
+        if sum != generated.metadata_sum || value != generated.root_value {
+            return Err(Box::new(GenericError::new(&format!(
+                "wrong answer for {:?}",
+                config
+            ))));
+        }
+        println!(
+            "{:>8} nodes: generate {:>10.3?}, parse {:>10.3?}, sum {:>10.3?}, value {:>10.3?}",
+            generated.num_nodes, generate_time, parse_time, sum_time, value_time
+        );
+    }
+    return Ok(());
+}
+
+fn print_stats(root: &Node) {
+    println!("nodes: {}", root.num_nodes());
+    println!("depth: {}", root.depth());
+    println!(
+        "leaves: {}",
+        root.post_order().filter(|n| n.children.is_empty()).count()
+    );
+    let mut level_sizes = vec![0; root.depth()];
+    for (depth, _) in root.by_depth() {
+        level_sizes[depth] += 1;
+    }
+    for (depth, size) in level_sizes.iter().enumerate() {
+        println!("nodes at depth {}: {}", depth, size);
+    }
+}
+
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let command = args.first().map(String::as_str);
+    match command {
+        None | Some("stats") => {
+            let root = Node::parse_str(&parselib::load_text_file("input.txt")?)?;
+            println!("part1: {}", root.sum_metadata());
+            println!("part2: {}", root.value());
+            if command == Some("stats") {
+                print_stats(&root);
+            }
+        }
+        Some("pretty") | Some("json") | Some("serialize") => {
+            let path = args.get(1).map_or("input.txt", String::as_str);
+            let root = parse_any(&parselib::load_text_file(path)?)?;
+            match command {
+                Some("pretty") => print!("{}", root.pretty_print()),
+                Some("json") => println!("{}", root.to_json()),
+                _ => println!("{}", root.serialize_string()),
+            }
+        }
+        Some("deep") => {
+            let depth = match args.get(1) {
+                Some(depth) => depth.parse()?,
+                None => 1_000_000,
+            };
+            check_deep_chain(depth)?;
+        }
+        Some("generate") => {
+            let generated = generate::generate(&parse_tree_config(&args[1..])?);
+            let numbers: Vec<String> = generated.numbers.iter().map(|n| n.to_string()).collect();
+            println!("{}", numbers.join(" "));
+            eprintln!(
+                "nodes: {}, metadata sum: {}, root value: {}",
+                generated.num_nodes, generated.metadata_sum, generated.root_value
+            );
+        }
+        Some("bench") => return bench(),
+        Some(command) => {
+            return Err(Box::new(GenericError::new(&format!(
+                "unknown command {} (expected stats, pretty, json, serialize, deep, generate \
+                 or bench)",
+                command
+            ))))
+        }
     }
-    println!("part1: {}", compute_sum_metadata(&mut input.iter().cloned())?);
-    println!("part2: {}", compute_node_value(&mut input.iter().cloned())?);
     Ok(())
 }
@@ -0,0 +1,321 @@
+use parselib::TokenError;
+use std::collections::VecDeque;
+use std::error;
+use std::fmt;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum LicenseError {
+    // A token that isn't a number
+    Token(TokenError),
+    // The stream ended where the token with this index was expected
+    Truncated { index: usize },
+    // Tokens from this index onwards aren't part of the tree
+    TrailingData { index: usize },
+    // The metadata entry at this index refers to child 0 of a node with children
+    ZeroReference { index: usize },
+}
+
+impl fmt::Display for LicenseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            LicenseError::Token(e) => write!(f, "{}", e),
+            LicenseError::Truncated { index } => {
+                write!(f, "Input ends early, expected token {}", index)
+            }
+            LicenseError::TrailingData { index } => {
+                write!(f, "Unexpected data after the tree at token {}", index)
+            }
+            LicenseError::ZeroReference { index } => {
+                write!(f, "Metadata at token {} refers to child 0", index)
+            }
+        }
+    }
+}
+
+impl error::Error for LicenseError {}
+
+impl From<TokenError> for LicenseError {
+    fn from(e: TokenError) -> LicenseError {
+        return LicenseError::Token(e);
+    }
+}
+
+struct Reader<'a> {
+    numbers: &'a [usize],
+    position: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, count: usize) -> Result<&'a [usize], LicenseError> {
+        if count > self.numbers.len() - self.position {
+            return Err(LicenseError::Truncated {
+                index: self.numbers.len(),
+            });
+        }
+        let result = &self.numbers[self.position..self.position + count];
+        self.position += count;
+        return Ok(result);
+    }
+
+    fn read_header(&mut self) -> Result<(usize, usize, Vec<Node>), LicenseError> {
+        let header = self.take(2)?;
+        let (num_children, num_metadata) = (header[0], header[1]);
+        // Every child needs at least a header, so don't trust larger counts
+        let remaining = self.numbers.len() - self.position;
+        if num_children > remaining / 2 {
+            return Err(LicenseError::Truncated {
+                index: self.numbers.len(),
+            });
+        }
+        return Ok((num_children, num_metadata, Vec::with_capacity(num_children)));
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct Node {
+    pub children: Vec<Node>,
+    pub metadata: Vec<usize>,
+}
+
+impl Node {
+    // Reads a whole stream of header and metadata numbers as one tree.  Nodes still being read are
+    // kept on an explicit stack rather than the call stack so that arbitrarily deep trees can be
+    // read.
+    pub fn parse(numbers: &[usize]) -> Result<Node, LicenseError> {
+        let mut reader = Reader {
+            numbers,
+            position: 0,
+        };
+        let mut stack = vec![reader.read_header()?];
+        loop {
+            let (num_children, num_metadata, children) = stack.last_mut().unwrap();
+            if children.len() < *num_children {
+                let header = reader.read_header()?;
+                stack.push(header);
+                continue;
+            }
+
+            let start = reader.position;
+            let metadata = reader.take(*num_metadata)?.to_vec();
+            if !children.is_empty() {
+                if let Some(i) = metadata.iter().position(|&m| m == 0) {
+                    return Err(LicenseError::ZeroReference { index: start + i });
+                }
+            }
+            let children = std::mem::take(children);
+            stack.pop();
+            let node = Node { children, metadata };
+            match stack.last_mut() {
+                Some((_, _, siblings)) => siblings.push(node),
+                None if reader.position < numbers.len() => {
+                    return Err(LicenseError::TrailingData {
+                        index: reader.position,
+                    })
+                }
+                None => return Ok(node),
+            }
+        }
+    }
+
+    pub fn parse_str(input: &str) -> Result<Node, LicenseError> {
+        return Node::parse(&parselib::parse_tokens(input)?);
+    }
+
+    pub fn sum_metadata(&self) -> usize {
+        return self
+            .pre_order()
+            .map(|node| node.metadata.iter().sum::<usize>())
+            .sum();
+    }
+
+    // Sum of the metadata for leaves, otherwise the sum of the values of the children the
+    // metadata refers to, counting from 1
+    pub fn value(&self) -> usize {
+        return *self.post_order_values().last().unwrap();
+    }
+
+    // Value of this node given the values of its children
+    pub fn value_from_children(&self, child_values: &[usize]) -> usize {
+        if self.children.is_empty() {
+            return self.metadata.iter().sum();
+        }
+        return self
+            .metadata
+            .iter()
+            .filter_map(|&m| m.checked_sub(1).and_then(|i| child_values.get(i)))
+            .sum();
+    }
+
+    // Value of every node in post-order.  Computed bottom up, where each node's children are the
+    // most recently finished values.
+    pub fn post_order_values(&self) -> Vec<usize> {
+        let mut values: Vec<usize> = Vec::new();
+        let mut pending: Vec<usize> = Vec::new();
+        for node in self.post_order() {
+            let child_values = pending.split_off(pending.len() - node.children.len());
+            let value = node.value_from_children(&child_values);
+            pending.push(value);
+            values.push(value);
+        }
+        return values;
+    }
+
+    pub fn num_nodes(&self) -> usize {
+        return self.pre_order().count();
+    }
+
+    // Number of levels in the tree, 1 for a single node
+    pub fn depth(&self) -> usize {
+        return self
+            .by_depth()
+            .map(|(depth, _)| depth + 1)
+            .max()
+            .unwrap_or(0);
+    }
+
+    pub fn pre_order(&self) -> PreOrder<'_> {
+        return PreOrder { stack: vec![self] };
+    }
+
+    pub fn post_order(&self) -> PostOrder<'_> {
+        return PostOrder {
+            stack: vec![(self, 0)],
+        };
+    }
+
+    // Nodes level by level, along with their depth, starting with this node at depth 0
+    pub fn by_depth(&self) -> ByDepth<'_> {
+        let mut queue = VecDeque::new();
+        queue.push_back((0, self));
+        return ByDepth { queue };
+    }
+}
+
+// The derived implementations would recurse once per level, so these walk the tree instead
+
+impl PartialEq for Node {
+    fn eq(&self, other: &Node) -> bool {
+        let mut lhs = self.pre_order();
+        let mut rhs = other.pre_order();
+        loop {
+            match (lhs.next(), rhs.next()) {
+                (Some(a), Some(b)) => {
+                    if a.metadata != b.metadata || a.children.len() != b.children.len() {
+                        return false;
+                    }
+                }
+                (None, None) => return true,
+                _ => return false,
+            }
+        }
+    }
+}
+
+impl Eq for Node {}
+
+impl Drop for Node {
+    fn drop(&mut self) {
+        let mut pending = std::mem::take(&mut self.children);
+        while let Some(mut node) = pending.pop() {
+            pending.append(&mut node.children);
+        }
+    }
+}
+
+pub struct PreOrder<'a> {
+    stack: Vec<&'a Node>,
+}
+
+impl<'a> Iterator for PreOrder<'a> {
+    type Item = &'a Node;
+
+    fn next(&mut self) -> Option<&'a Node> {
+        let node = self.stack.pop()?;
+        self.stack.extend(node.children.iter().rev());
+        return Some(node);
+    }
+}
+
+pub struct PostOrder<'a> {
+    // Each node along with how many of its children have been visited
+    stack: Vec<(&'a Node, usize)>,
+}
+
+impl<'a> Iterator for PostOrder<'a> {
+    type Item = &'a Node;
+
+    fn next(&mut self) -> Option<&'a Node> {
+        loop {
+            let (node, next_child) = self.stack.last_mut()?;
+            let node: &'a Node = node;
+            if let Some(child) = node.children.get(*next_child) {
+                *next_child += 1;
+                self.stack.push((child, 0));
+            } else {
+                self.stack.pop();
+                return Some(node);
+            }
+        }
+    }
+}
+
+pub struct ByDepth<'a> {
+    queue: VecDeque<(usize, &'a Node)>,
+}
+
+impl<'a> Iterator for ByDepth<'a> {
+    type Item = (usize, &'a Node);
+
+    fn next(&mut self) -> Option<(usize, &'a Node)> {
+        let (depth, node) = self.queue.pop_front()?;
+        self.queue
+            .extend(node.children.iter().map(|child| (depth + 1, child)));
+        return Some((depth, node));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn puzzle_example() {
+        let root = Node::parse_str("2 3 0 3 10 11 12 1 1 0 1 99 2 1 1 2").unwrap();
+        assert_eq!(root.sum_metadata(), 138);
+        assert_eq!(root.value(), 66);
+        assert_eq!(root.num_nodes(), 4);
+        assert_eq!(root.depth(), 3);
+    }
+
+    // Runs on a test thread's small stack, so anything recursing once per level would overflow
+    // long before the end
+    #[test]
+    fn parses_and_drops_a_deep_chain() {
+        let root = Node::parse(&crate::deep_chain(1_000_000)).unwrap();
+        assert_eq!(root.sum_metadata(), 1_000_000);
+        assert_eq!(root.value(), 1);
+        assert_eq!(root.depth(), 1_000_000);
+        assert!(root == Node::parse(&crate::deep_chain(1_000_000)).unwrap());
+        drop(root);
+    }
+
+    #[test]
+    fn errors() {
+        assert_eq!(
+            Node::parse(&[1, 1, 0]),
+            Err(LicenseError::Truncated { index: 3 })
+        );
+        assert_eq!(
+            Node::parse(&[0, 1, 5, 7]),
+            Err(LicenseError::TrailingData { index: 3 })
+        );
+        assert_eq!(
+            Node::parse(&[1, 1, 0, 1, 5, 0]),
+            Err(LicenseError::ZeroReference { index: 5 })
+        );
+        assert!(matches!(
+            Node::parse_str("0 1 x"),
+            Err(LicenseError::Token(_))
+        ));
+    }
+}
@@ -1,9 +1,12 @@
+#![allow(clippy::needless_return)]
+
 use std::error;
 use std::fmt;
 use std::fs::File;
 use std::io::Read;
 use std::path::Path;
 use std::str::FromStr;
+use std::time::Duration;
 
 #[derive(Debug, Clone, Copy)]
 pub struct ParseError;
@@ -42,6 +45,33 @@ impl fmt::Display for GenericError {
 impl error::Error for GenericError {
 }
 
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TokenError {
+    pub index: usize,
+    pub token: String,
+}
+
+impl fmt::Display for TokenError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Error parsing token {}: {:?}", self.index, self.token)
+    }
+}
+
+impl error::Error for TokenError {
+}
+
+// Parses every whitespace separated token, however the tokens are spaced or split across lines
+pub fn parse_tokens<V: FromStr>(input: &str) -> Result<Vec<V>, TokenError> {
+    let mut result = Vec::new();
+    for (index, token) in input.split_whitespace().enumerate() {
+        result.push(token.parse().map_err(|_| TokenError {
+            index,
+            token: String::from(token),
+        })?);
+    }
+    return Ok(result);
+}
+
 pub fn parse_lines<V: FromStr, P: AsRef<Path>>(path: P) -> Result<Vec<V>, ParseError> {
     let input_str = load_text_file(path)?;
     let mut result = Vec::new();
@@ -81,3 +111,53 @@ pub fn load_bytes<P: AsRef<Path>>(path: P) -> Result<Vec<u8>, ParseError> {
 
     return Ok(result);
 }
+
+// Small seeded xorshift generator for synthetic inputs in benchmarks and generated tests.  Not
+// suitable for anything that needs good randomness, but the same seed always gives the same
+// sequence.
+#[derive(Debug, Clone)]
+pub struct Random {
+    state: u64,
+}
+
+impl Random {
+    pub fn new(seed: u64) -> Random {
+        // xorshift gets stuck at zero, and nearby seeds give similar first outputs without mixing
+        return Random {
+            state: seed.wrapping_mul(0x9e37_79b9_7f4a_7c15) | 1,
+        };
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        self.state ^= self.state << 13;
+        self.state ^= self.state >> 7;
+        self.state ^= self.state << 17;
+        return self.state;
+    }
+
+    // Uniform-ish number in 0..bound, which must not be zero
+    pub fn below(&mut self, bound: u64) -> u64 {
+        return self.next_u64() % bound;
+    }
+
+    // Number in lo..=hi, or lo if the range is empty
+    pub fn range(&mut self, lo: i64, hi: i64) -> i64 {
+        if hi <= lo {
+            return lo;
+        }
+        let span = (hi as i128 - lo as i128 + 1) as u128;
+        return (lo as i128 + (self.next_u64() as u128 % span) as i128) as i64;
+    }
+}
+
+// Timings of a fast and a slow way of getting the same answer side by side, for benchmarks
+pub fn compare_times(fast_name: &str, fast: Duration, slow_name: &str, slow: Duration) -> String {
+    return format!(
+        "{} {:>10.3?}, {} {:>10.3?} ({:.1}x)",
+        fast_name,
+        fast,
+        slow_name,
+        slow,
+        slow.as_secs_f64() / fast.as_secs_f64()
+    );
+}